@@ -1,12 +1,12 @@
 #!/bin/bash
 
 # Requires Downloads/oidn-1.1.0.x86_64.macos in downloads feild
-# Requires imagemagick installed in system
 # Modify for your use.
+#
+# Render straight to .pfm (i.e. render(scene, "./image.pfm".into())) so the
+# denoiser gets the full float values without a round trip through ImageMagick.
 
-for f in ./*.png; do
-    convert -endian LSB $f "${f%.png}.pfm" &&
-    ~/Downloads/oidn-1.1.0.x86_64.macos/bin/denoise -ldr "${f%.png}.pfm" -o "${f%.png}-denoise.pfm" &&
-    convert "${f%.png}-denoise.pfm" $f &&
-    rm *.pfm
+for f in ./*.pfm; do
+    case $f in *-denoise.pfm) continue;; esac
+    ~/Downloads/oidn-1.1.0.x86_64.macos/bin/denoise -hdr "$f" -o "${f%.pfm}-denoise.pfm"
 done
@@ -42,6 +42,7 @@ pub fn cornell_box() -> Scene {
         k: 554.0,
         material: light.clone(),
     }
+    .flip_normals()
     .push_into_list_of_boxed_hitables(&mut list);
 
     XZRect {
@@ -117,13 +118,7 @@ pub fn cornell_box() -> Scene {
         vfow: 40.0,
     });
 
-    Scene {
-        nx,
-        ny,
-        ns,
-        cam,
-        world,
-    }
+    Scene::new(nx, ny, ns, cam, world)
 }
 
 fn main() {
@@ -60,13 +60,7 @@ pub fn earth() -> Scene {
         vfow: 40.0,
     });
 
-    Scene {
-        nx,
-        ny,
-        ns,
-        cam,
-        world,
-    }
+    Scene::new(nx, ny, ns, cam, world)
 }
 
 fn main() {
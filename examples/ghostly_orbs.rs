@@ -9,17 +9,27 @@ use std::sync::Arc;
 type List = Vec<BoxHitable>;
 static GLASS_SPHERE_RADIUS: f32 = 75.0;
 
-fn cube_light(center: Vec3, list: &mut List) {
+fn cube_light(cube: &Arc<dyn Hitable>, center: Vec3, list: &mut List) {
     let brightness = center.y * 2.0 / 100.0 + 0.01;
-    Cuboid::cube(
-        12.0,
-        center,
-        material::diffuse_light(1.5 * brightness, 1.5 * brightness, 1.5 * brightness),
+    Instance::new(
+        cube.clone(),
+        Transform::try_new(Mat4::translation(center)).unwrap(),
     )
+    .with_material(Arc::new(material::diffuse_light(
+        1.5 * brightness,
+        1.5 * brightness,
+        1.5 * brightness,
+    )))
     .push_into_list_of_boxed_hitables(list);
 }
 
 fn cube_grid(list: &mut List, time: f32) {
+    // Every cube shares this one, each with its own brightness
+    let cube: Arc<dyn Hitable> = Arc::new(Cuboid::cube(
+        12.0,
+        Vec3::new(0.0, 0.0, 0.0),
+        material::lambertion(1.0, 1.0, 1.0),
+    ));
     for x in -48..48 {
         for z in -48..48 {
             let distance_increment = 75.0;
@@ -32,7 +42,7 @@ fn cube_grid(list: &mut List, time: f32) {
             }
 
             let y = (distance * 0.005 - (time + 2.0) * 2.5).cos() * 100.0 - 30.0;
-            cube_light(Vec3::new(x, y, z), list);
+            cube_light(&cube, Vec3::new(x, y, z), list);
         }
     }
 }
@@ -50,6 +60,7 @@ fn ghostly_orbs(time: f32) -> Arc<dyn Hitable> {
         k: 1500.0,
         material: light.clone(),
     }
+    .flip_normals()
     .push_into_list_of_boxed_hitables(&mut list);
 
     // Floor
@@ -154,13 +165,7 @@ fn ghostly_orbs_view_1(time: f32) -> Scene {
         vfow: 40.0,
     });
 
-    Scene {
-        nx,
-        ny,
-        ns,
-        cam,
-        world: ghostly_orbs(time),
-    }
+    Scene::new(nx, ny, ns, cam, ghostly_orbs(time))
 }
 
 #[allow(dead_code)]
@@ -185,13 +190,7 @@ fn ghostly_orbs_view_2(time: f32) -> Scene {
         vfow: 40.0,
     });
 
-    Scene {
-        nx,
-        ny,
-        ns,
-        cam,
-        world: ghostly_orbs(time),
-    }
+    Scene::new(nx, ny, ns, cam, ghostly_orbs(time))
 }
 
 fn main() {
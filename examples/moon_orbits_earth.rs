@@ -70,13 +70,14 @@ fn earth(time: f32) -> Scene {
         vfow: 40.0,
     });
 
-    Scene {
-        nx,
-        ny,
-        ns,
-        cam,
-        world,
-    }
+    // The sun is far brighter than anything else, let the filmic curve roll it off
+    let mut scene = Scene::new(nx, ny, ns, cam, world);
+    scene.tone_map = ToneMap {
+        exposure: -0.5,
+        operator: ToneMapOperator::Aces,
+        oetf: Oetf::Srgb,
+    };
+    scene
 }
 
 pub fn moon_orbits_earth() {
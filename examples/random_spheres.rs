@@ -20,7 +20,7 @@ fn gen_coords() -> [(i32, i32); 484] {
 }
 
 fn gen_random_spheres() -> Vec<Box<dyn Hitable>> {
-    let mut rng = thread_rng();
+    let mut rng = Pcg32::new(2019, 0);
     let mut rnd = || rng.gen::<f32>();
     let fl = |i: &i32| *i as f32;
     let mut list: Vec<Box<dyn Hitable>> = Vec::with_capacity(484);
@@ -146,13 +146,7 @@ pub fn random_spheres() -> Scene {
         vfow: 40.0,
     });
 
-    Scene {
-        nx,
-        ny,
-        ns,
-        cam,
-        world: Arc::new(BVHNode::new(list)),
-    }
+    Scene::new(nx, ny, ns, cam, Arc::new(BVHNode::new(list)))
 }
 
 fn main() {
@@ -9,7 +9,7 @@ use std::sync::Arc;
 
 fn rttnw_final_world() -> Arc<dyn Hitable> {
     let mut list: Vec<BoxHitable> = Vec::with_capacity(429);
-    let mut rng = thread_rng();
+    let mut rng = Pcg32::new(2019, 0);
     let mut rand = || rng.gen::<f32>();
     let white = material::lambertion(0.73, 0.73, 0.73);
     let ground = material::lambertion(0.48, 0.83, 0.53);
@@ -42,6 +42,7 @@ fn rttnw_final_world() -> Arc<dyn Hitable> {
         k: 554.0,
         material: light.clone(),
     }
+    .flip_normals()
     .push_into_list_of_boxed_hitables(&mut list);
 
     Sphere {
@@ -134,13 +135,7 @@ pub fn example_scene() -> Scene {
         vfow: 40.0,
     });
 
-    Scene {
-        nx,
-        ny,
-        ns,
-        cam,
-        world: rttnw_final_world(),
-    }
+    Scene::new(nx, ny, ns, cam, rttnw_final_world())
 }
 
 fn main() {
@@ -63,13 +63,7 @@ pub fn simple_light() -> Scene {
         vfow: 40.0,
     });
 
-    Scene {
-        nx,
-        ny,
-        ns,
-        cam,
-        world,
-    }
+    Scene::new(nx, ny, ns, cam, world)
 }
 
 fn main() {
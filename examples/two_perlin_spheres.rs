@@ -44,13 +44,7 @@ pub fn two_perlin_spheres() -> Scene {
         vfow: 40.0,
     });
 
-    Scene {
-        nx,
-        ny,
-        ns,
-        cam,
-        world,
-    }
+    Scene::new(nx, ny, ns, cam, world)
 }
 
 fn main() {
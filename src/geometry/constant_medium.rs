@@ -26,7 +26,9 @@ impl Hitable for ConstantMedium {
         if rec1.t < 0.0 {
             rec1.t = 0.0
         };
-        let rval = thread_rng().gen::<f32>();
+        // Hit tests run any number of times per sample, so they draw from the
+        // sample's random stream rather than using up sampler dimensions
+        let rval = sample_rng().gen::<f32>();
         let distance_inside_boundry = (rec2.t - rec1.t) * r.direction.length();
         let hit_distance = -(1.0 / self.density) * rval.ln();
         if hit_distance < distance_inside_boundry {
@@ -38,6 +40,8 @@ impl Hitable for ConstantMedium {
                 p: r.point_at_parameter(t),
                 normal: Vec3::new(1.0, 0.0, 0.0),
                 material: &self.phase_function,
+                object_id: 0,
+                vertex_color: None,
             })
         } else {
             None
@@ -1,4 +1,4 @@
-use super::super::{material::Material, tracer::*};
+use super::super::{light::LightBounds, material::Material, tracer::*};
 use super::{
     rect::{XYRect, XZRect, YZRect},
     translation::Translation,
@@ -112,6 +112,32 @@ impl Hitable for Cuboid {
     fn box_clone(&self) -> BoxHitable {
         Box::new(self.clone())
     }
+
+    fn sample(&self, origin: Vec3, u: (f32, f32)) -> Option<Vec3> {
+        self.rects.sample(origin, u)
+    }
+
+    fn pdf(&self, origin: Vec3, direction: Vec3) -> f32 {
+        self.rects.pdf(origin, direction)
+    }
+
+    /// Each face is its own light, faces turned away from a point are hidden
+    /// behind the others and so are never counted twice
+    fn lights(&self) -> Vec<BoxHitable> {
+        self.rects.lights()
+    }
+
+    fn area(&self) -> f32 {
+        self.rects.area()
+    }
+
+    fn transformed_area(&self, transform: &Transform) -> f32 {
+        self.rects.transformed_area(transform)
+    }
+
+    fn light_bounds(&self) -> Option<LightBounds> {
+        self.rects.light_bounds()
+    }
 }
 
 impl Translation for Cuboid {}
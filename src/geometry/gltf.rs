@@ -0,0 +1,812 @@
+use super::super::material::{self, Material};
+use super::super::texture::{BoxTexture, ConstantTexture, ImageTexture, VertexColorTexture};
+use super::super::tracer::*;
+use super::instance::Instance;
+use super::triangle_mesh::{MeshBuffers, TriangleMesh};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Load a .gltf or .glb file as a scene of nx by ny pixels with ns samples
+/// per pixel
+pub fn read_gltf(path: &str, nx: i32, ny: i32, ns: i32) -> io::Result<Scene> {
+    let data = fs::read(path)?;
+    let dir = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+    decode_gltf(&data, dir, nx, ny, ns)
+}
+
+/// Decode the contents of a .gltf or .glb file, looking up external buffers
+/// and images in dir. Meshes are placed by the transforms of their nodes, and
+/// the scene is seen through the first perspective camera in the node
+/// hierarchy, or from in front of the meshes if there is none.
+pub fn decode_gltf(data: &[u8], dir: &Path, nx: i32, ny: i32, ns: i32) -> io::Result<Scene> {
+    let (json, bin) = if data.starts_with(b"glTF") {
+        split_glb(data)?
+    } else {
+        (data, None)
+    };
+    let document: Value = serde_json::from_slice(json).map_err(|e| invalid_gltf(&e.to_string()))?;
+
+    let mut buffers = Vec::new();
+    if let Some(list) = document["buffers"].as_array() {
+        for (i, buffer) in list.iter().enumerate() {
+            buffers.push(match (buffer["uri"].as_str(), bin) {
+                (Some(uri), _) => load_uri(uri, dir)?,
+                // The binary chunk of a .glb is its first buffer
+                (None, Some(bin)) if i == 0 => bin.to_vec(),
+                _ => return Err(invalid_gltf("buffer without data")),
+            });
+        }
+    }
+
+    let roots: Vec<usize> =
+        match document["scenes"][gltf_index(&document["scene"]).unwrap_or(0)]["nodes"].as_array() {
+            Some(nodes) => nodes.iter().filter_map(gltf_index).collect(),
+            // Without scenes every node that isn't a child is a root
+            None => {
+                let nodes = document["nodes"].as_array().map_or(&[][..], |nodes| nodes);
+                let children: Vec<usize> = nodes
+                    .iter()
+                    .filter_map(|node| node["children"].as_array())
+                    .flatten()
+                    .filter_map(gltf_index)
+                    .collect();
+                (0..nodes.len()).filter(|i| !children.contains(i)).collect()
+            }
+        };
+
+    let mut mesh_uses = HashMap::new();
+    if let Some(nodes) = document["nodes"].as_array() {
+        for mesh in nodes.iter().filter_map(|node| gltf_index(&node["mesh"])) {
+            *mesh_uses.entry(mesh).or_insert(0) += 1;
+        }
+    }
+
+    let mut gltf = Gltf {
+        document,
+        buffers,
+        dir,
+        aspect: nx as f32 / ny as f32,
+        images: HashMap::new(),
+        mesh_uses,
+        prototypes: HashMap::new(),
+        meshes: Vec::new(),
+        camera: None,
+    };
+    for root in roots {
+        gltf.visit(root, &Mat4::identity(), 0)?;
+    }
+    if gltf.meshes.is_empty() {
+        return Err(invalid_gltf("no triangles in the scene"));
+    }
+
+    let world = BVHNode::new(gltf.meshes);
+    let camera = match gltf.camera {
+        Some(camera) => camera,
+        None => framing_camera(&world, gltf.aspect),
+    };
+    Ok(Scene::new(nx, ny, ns, Camera::new(camera), Arc::new(world)))
+}
+
+/// A glTF document being turned into hitables
+struct Gltf<'a> {
+    document: Value,
+    buffers: Vec<Vec<u8>>,
+    dir: &'a Path,
+    aspect: f32,
+    /// Decoded images by index
+    images: HashMap<usize, ImageTexture>,
+    /// Number of nodes using each mesh
+    mesh_uses: HashMap<usize, usize>,
+    /// Meshes shared by several nodes, by index
+    prototypes: HashMap<usize, Vec<Arc<dyn Hitable>>>,
+    meshes: Vec<BoxHitable>,
+    camera: Option<CameraOpts>,
+}
+
+impl<'a> Gltf<'a> {
+    /// Add a node and its children, placed by parent
+    fn visit(&mut self, index: usize, parent: &Mat4, depth: usize) -> io::Result<()> {
+        let node = self.document["nodes"][index].clone();
+        if !node.is_object() {
+            return Err(invalid_gltf("missing node"));
+        }
+        if depth
+            > self.document["nodes"]
+                .as_array()
+                .map_or(0, |nodes| nodes.len())
+        {
+            return Err(invalid_gltf("nodes are their own ancestors"));
+        }
+
+        let matrix = *parent * node_matrix(&node);
+        if let Some(mesh) = gltf_index(&node["mesh"]) {
+            self.add_mesh(mesh, &matrix)?;
+        }
+        if self.camera.is_none() {
+            if let Some(camera) = gltf_index(&node["camera"]) {
+                self.camera = self.camera_opts(camera, &matrix);
+            }
+        }
+        if let Some(children) = node["children"].as_array() {
+            for child in children.iter().filter_map(gltf_index) {
+                self.visit(child, &matrix, depth + 1)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Add the primitives of a mesh placed by matrix. Meshes used by several
+    /// nodes are loaded once and shared between them.
+    fn add_mesh(&mut self, index: usize, matrix: &Mat4) -> io::Result<()> {
+        if self.mesh_uses.get(&index).cloned().unwrap_or(0) < 2 {
+            for mesh in self.mesh(index, matrix)? {
+                self.meshes.push(Box::new(mesh));
+            }
+            return Ok(());
+        }
+
+        // Nodes scaled down to nothing have no surface
+        let transform = match Transform::try_new(*matrix) {
+            Some(transform) => transform,
+            None => return Ok(()),
+        };
+        if !self.prototypes.contains_key(&index) {
+            let prototypes = self
+                .mesh(index, &Mat4::identity())?
+                .into_iter()
+                .map(|mesh| Arc::new(mesh) as Arc<dyn Hitable>)
+                .collect();
+            self.prototypes.insert(index, prototypes);
+        }
+        for prototype in &self.prototypes[&index] {
+            self.meshes
+                .push(Box::new(Instance::new(prototype.clone(), transform)));
+        }
+        Ok(())
+    }
+
+    /// A mesh for each triangle primitive, with its vertices moved by matrix
+    fn mesh(&mut self, index: usize, matrix: &Mat4) -> io::Result<Vec<TriangleMesh>> {
+        let mesh = self.document["meshes"][index].clone();
+        let primitives = mesh["primitives"]
+            .as_array()
+            .ok_or_else(|| invalid_gltf("mesh without primitives"))?;
+        let normal_matrix = match matrix.inverse() {
+            Some(inverse) => inverse.transpose(),
+            None => return Ok(Vec::new()),
+        };
+        let mirrored = matrix.determinant() < 0.0;
+
+        let mut meshes = Vec::new();
+        for primitive in primitives {
+            // Points and lines have no surface to hit
+            let mode = primitive["mode"].as_u64().unwrap_or(4);
+            if mode < 4 {
+                continue;
+            }
+
+            let attributes = &primitive["attributes"];
+            let position = gltf_index(&attributes["POSITION"])
+                .ok_or_else(|| invalid_gltf("primitive without positions"))?;
+            let positions: Vec<Vec3> = self
+                .accessor(position)?
+                .iter()
+                .map(|p| matrix.transform_point(vec3(p)))
+                .collect();
+            let normals: Vec<Vec3> = self
+                .attribute(attributes, "NORMAL")?
+                .iter()
+                .map(|n| normal_matrix.transform_vector(vec3(n)))
+                .collect();
+            // glTF images start at the top, ImageTexture's at the bottom
+            let uvs: Vec<(f32, f32)> = self
+                .attribute(attributes, "TEXCOORD_0")?
+                .iter()
+                .map(|t| (t[0] as f32, 1.0 - t[1] as f32))
+                .collect();
+            let colors: Vec<Vec3> = self
+                .attribute(attributes, "COLOR_0")?
+                .iter()
+                .map(vec3)
+                .collect();
+
+            let count = positions.len();
+            let lengths = [normals.len(), uvs.len(), colors.len()];
+            if lengths.iter().any(|&length| length != 0 && length != count) {
+                return Err(invalid_gltf("attributes of different lengths"));
+            }
+            let corners: Vec<usize> = match gltf_index(&primitive["indices"]) {
+                Some(indices) => self
+                    .accessor(indices)?
+                    .iter()
+                    .map(|i| i[0] as usize)
+                    .collect(),
+                None => (0..count).collect(),
+            };
+            if corners.iter().any(|&corner| corner >= count) {
+                return Err(invalid_gltf("index out of range"));
+            }
+            let buffers = MeshBuffers {
+                positions,
+                normals,
+                uvs,
+                colors,
+                indices: triangles(&corners, mode, mirrored),
+            };
+
+            let has_colors = !buffers.colors.is_empty();
+            let material = self.material(gltf_index(&primitive["material"]), has_colors)?;
+            let mesh = TriangleMesh::new(buffers, material);
+            if mesh.triangle_count() > 0 {
+                meshes.push(mesh);
+            }
+        }
+        Ok(meshes)
+    }
+
+    /// The elements of a primitive's attribute, or none if it doesn't have it
+    fn attribute(&self, attributes: &Value, name: &str) -> io::Result<Vec<[f64; 4]>> {
+        match gltf_index(&attributes[name]) {
+            Some(accessor) => self.accessor(accessor),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// The elements of an accessor, padded to four components. Normalized
+    /// integers are mapped to [0, 1] or [-1, 1].
+    fn accessor(&self, index: usize) -> io::Result<Vec<[f64; 4]>> {
+        let accessor = &self.document["accessors"][index];
+        let count = gltf_index(&accessor["count"])
+            .ok_or_else(|| invalid_gltf("accessor without a count"))?;
+        let components = match accessor["type"].as_str() {
+            Some("SCALAR") => 1,
+            Some("VEC2") => 2,
+            Some("VEC3") => 3,
+            Some("VEC4") => 4,
+            _ => return Err(invalid_gltf("unsupported accessor type")),
+        };
+        let component_type = accessor["componentType"].as_u64().unwrap_or(0);
+        let (size, max) = match component_type {
+            5120 => (1, 127.0),
+            5121 => (1, 255.0),
+            5122 => (2, 32767.0),
+            5123 => (2, 65535.0),
+            5125 => (4, 4_294_967_295.0),
+            5126 => (4, 1.0),
+            _ => return Err(invalid_gltf("unsupported component type")),
+        };
+        if !accessor["sparse"].is_null() {
+            return Err(invalid_gltf("sparse accessors aren't supported"));
+        }
+
+        // Accessors without a buffer view are all zeros. Nothing backs them,
+        // so the file's size caps how many elements they can claim.
+        let view = match gltf_index(&accessor["bufferView"]) {
+            Some(view) => &self.document["bufferViews"][view],
+            None if count <= self.buffers.iter().map(Vec::len).sum() => {
+                return Ok(vec![[0.0; 4]; count]);
+            }
+            None => return Err(invalid_gltf("accessor larger than the file")),
+        };
+        let buffer = gltf_index(&view["buffer"])
+            .and_then(|buffer| self.buffers.get(buffer))
+            .ok_or_else(|| invalid_gltf("missing buffer"))?;
+        let offset = gltf_index(&view["byteOffset"])
+            .unwrap_or(0)
+            .checked_add(gltf_index(&accessor["byteOffset"]).unwrap_or(0));
+        let stride = gltf_index(&view["byteStride"]).unwrap_or(size * components);
+        let normalized = accessor["normalized"].as_bool().unwrap_or(false);
+
+        // Check the last element fits before allocating for them all, which
+        // also keeps the offsets below from overflowing
+        let end = match count.checked_sub(1) {
+            Some(last) => last
+                .checked_mul(stride)
+                .and_then(|start| start.checked_add(offset?))
+                .and_then(|start| start.checked_add(size * components)),
+            None => offset,
+        };
+        if end.is_none_or(|end| end > buffer.len()) {
+            return Err(invalid_gltf("accessor past the end of its buffer"));
+        }
+        let offset = offset.unwrap_or(0);
+
+        let mut values = vec![[0.0; 4]; count];
+        for (i, value) in values.iter_mut().enumerate() {
+            for (c, component) in value.iter_mut().take(components).enumerate() {
+                let start = offset + i * stride + c * size;
+                let b = buffer
+                    .get(start..start + size)
+                    .ok_or_else(|| invalid_gltf("accessor past the end of its buffer"))?;
+                let raw = match component_type {
+                    5120 => f64::from(b[0] as i8),
+                    5121 => f64::from(b[0]),
+                    5122 => f64::from(i16::from_le_bytes([b[0], b[1]])),
+                    5123 => f64::from(u16::from_le_bytes([b[0], b[1]])),
+                    5125 => f64::from(u32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+                    _ => f64::from(f32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+                };
+                *component = if normalized {
+                    (raw / max).max(-1.0)
+                } else {
+                    raw
+                };
+            }
+        }
+        Ok(values)
+    }
+
+    /// The closest Material to a metallic-roughness material. Emissive
+    /// materials become lights, transmissive ones dielectrics with their
+    /// `KHR_materials_ior` index, mostly metallic ones metals with their
+    /// roughness as fuzz and the rest Lambertian. Primitives without a
+    /// material get a light gray Lambertian.
+    fn material(&mut self, index: Option<usize>, vertex_colors: bool) -> io::Result<Material> {
+        let material = match index {
+            Some(index) => self.document["materials"][index].clone(),
+            None if vertex_colors => {
+                return Ok(Material::Lambertion {
+                    albedo: Box::new(VertexColorTexture),
+                })
+            }
+            None => return Ok(material::lambertion(0.8, 0.8, 0.8)),
+        };
+        let pbr = &material["pbrMetallicRoughness"];
+        let extensions = &material["extensions"];
+
+        let strength = extensions["KHR_materials_emissive_strength"]["emissiveStrength"]
+            .as_f64()
+            .unwrap_or(1.0) as f32;
+        let emission = strength * color(&material["emissiveFactor"], 0.0);
+        if luminance(emission) > 0.0 {
+            // The texture holds the color, the intensity how bright it is
+            let intensity = emission.x.max(emission.y).max(emission.z);
+            return Ok(
+                match self.texture(&material["emissiveTexture"], emission / intensity)? {
+                    Some(texture) => material::textured_light(texture, intensity),
+                    None => material::diffuse_light(emission.x, emission.y, emission.z),
+                },
+            );
+        }
+
+        let transmission = extensions["KHR_materials_transmission"]["transmissionFactor"]
+            .as_f64()
+            .unwrap_or(0.0);
+        if transmission >= 0.5 {
+            let ior = extensions["KHR_materials_ior"]["ior"]
+                .as_f64()
+                .unwrap_or(1.5);
+            return Ok(material::dielectric(ior as f32));
+        }
+
+        let base_color = color(&pbr["baseColorFactor"], 1.0);
+        if pbr["metallicFactor"].as_f64().unwrap_or(1.0) >= 0.5 {
+            let roughness = pbr["roughnessFactor"].as_f64().unwrap_or(1.0);
+            return Ok(material::metal(base_color, roughness as f32));
+        }
+
+        // Vertex colors stand in for the base color factor
+        let albedo = match self.texture(&pbr["baseColorTexture"], base_color)? {
+            Some(texture) => texture,
+            None if vertex_colors => Box::new(VertexColorTexture),
+            None => Box::new(ConstantTexture::new(
+                base_color.x,
+                base_color.y,
+                base_color.z,
+            )),
+        };
+        Ok(Material::Lambertion { albedo })
+    }
+
+    /// The image a texture info refers to, with factor baked into its pixels
+    fn texture(&mut self, info: &Value, factor: Vec3) -> io::Result<Option<BoxTexture>> {
+        let texture = match gltf_index(&info["index"]) {
+            Some(texture) => texture,
+            None => return Ok(None),
+        };
+        let source = gltf_index(&self.document["textures"][texture]["source"])
+            .ok_or_else(|| invalid_gltf("texture without an image"))?;
+        let mut texture = self.image(source)?;
+        if factor.x != 1.0 || factor.y != 1.0 || factor.z != 1.0 {
+            for pixel in texture.image.chunks_mut(3) {
+                pixel[0] = (f32::from(pixel[0]) * factor.x).min(255.0) as u8;
+                pixel[1] = (f32::from(pixel[1]) * factor.y).min(255.0) as u8;
+                pixel[2] = (f32::from(pixel[2]) * factor.z).min(255.0) as u8;
+            }
+        }
+        Ok(Some(Box::new(texture)))
+    }
+
+    fn image(&mut self, index: usize) -> io::Result<ImageTexture> {
+        if let Some(texture) = self.images.get(&index) {
+            return Ok(texture.clone());
+        }
+
+        let entry = &self.document["images"][index];
+        let data = match (entry["uri"].as_str(), gltf_index(&entry["bufferView"])) {
+            (Some(uri), _) => load_uri(uri, self.dir)?,
+            (None, Some(view)) => self.buffer_view(view)?.to_vec(),
+            _ => return Err(invalid_gltf("image without data")),
+        };
+        let image = image::load_from_memory(&data)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?
+            .to_rgb();
+        let (nx, ny) = image.dimensions();
+        let texture = ImageTexture {
+            image: image.into_raw(),
+            nx,
+            ny,
+        };
+        self.images.insert(index, texture.clone());
+        Ok(texture)
+    }
+
+    fn buffer_view(&self, index: usize) -> io::Result<&[u8]> {
+        let view = &self.document["bufferViews"][index];
+        let start = gltf_index(&view["byteOffset"]).unwrap_or(0);
+        let length = gltf_index(&view["byteLength"]).unwrap_or(0);
+        gltf_index(&view["buffer"])
+            .and_then(|buffer| self.buffers.get(buffer))
+            .and_then(|buffer| buffer.get(start..start.checked_add(length)?))
+            .ok_or_else(|| invalid_gltf("buffer view past the end of its buffer"))
+    }
+
+    /// A perspective camera placed by matrix, looking down its -z axis
+    fn camera_opts(&self, index: usize, matrix: &Mat4) -> Option<CameraOpts> {
+        let yfov = self.document["cameras"][index]["perspective"]["yfov"].as_f64()? as f32;
+        Some(CameraOpts {
+            lookfrom: matrix.transform_point(Vec3::new(0.0, 0.0, 0.0)),
+            lookat: matrix.transform_point(Vec3::new(0.0, 0.0, -1.0)),
+            vup: matrix.transform_vector(Vec3::new(0.0, 1.0, 0.0)),
+            vfow: yfov.to_degrees(),
+            aspect: self.aspect,
+            aperture: 0.0,
+            focus_dist: 1.0,
+        })
+    }
+}
+
+/// A camera looking down -z at the whole of world
+fn framing_camera(world: &dyn Hitable, aspect: f32) -> CameraOpts {
+    let (center, radius) = match world.bounding_box() {
+        Some(bbox) => (
+            0.5 * (bbox.min + bbox.max),
+            0.5 * (bbox.max - bbox.min).length(),
+        ),
+        None => (Vec3::new(0.0, 0.0, 0.0), 1.0),
+    };
+    let vfow: f32 = 40.0;
+    let half_height = (0.5 * vfow).to_radians().tan();
+    // Narrow images fit the world across rather than up and down
+    let half_angle = half_height.min(aspect * half_height).atan();
+    let distance = radius / half_angle.sin();
+    CameraOpts {
+        lookfrom: center + Vec3::new(0.0, 0.0, distance),
+        lookat: center,
+        vup: Vec3::new(0.0, 1.0, 0.0),
+        vfow,
+        aspect,
+        aperture: 0.0,
+        focus_dist: 1.0,
+    }
+}
+
+/// Triangles from the corners of a triangle list, strip or fan, wound
+/// the other way when the mesh is mirrored
+fn triangles(corners: &[usize], mode: u64, mirrored: bool) -> Vec<[usize; 3]> {
+    let mut triangles: Vec<[usize; 3]> = match mode {
+        5 => corners
+            .windows(3)
+            .enumerate()
+            .map(|(i, w)| {
+                // Every other triangle of a strip is wound backwards
+                if i % 2 == 0 {
+                    [w[0], w[1], w[2]]
+                } else {
+                    [w[1], w[0], w[2]]
+                }
+            })
+            .collect(),
+        6 => corners
+            .windows(2)
+            .skip(1)
+            .map(|w| [corners[0], w[0], w[1]])
+            .collect(),
+        _ => corners
+            .chunks_exact(3)
+            .map(|c| [c[0], c[1], c[2]])
+            .collect(),
+    };
+    if mirrored {
+        for triangle in &mut triangles {
+            triangle.swap(1, 2);
+        }
+    }
+    triangles
+}
+
+/// The local transform of a node, from its matrix or its translation,
+/// rotation and scale
+fn node_matrix(node: &Value) -> Mat4 {
+    if let Some(values) = node["matrix"].as_array() {
+        let values: Vec<f32> = values
+            .iter()
+            .map(|v| v.as_f64().unwrap_or(0.0) as f32)
+            .collect();
+        if values.len() == 16 {
+            // Stored column by column
+            let mut matrix = Mat4::identity();
+            for (i, value) in values.into_iter().enumerate() {
+                matrix.m[i % 4][i / 4] = value;
+            }
+            return matrix;
+        }
+    }
+
+    let t = floats(&node["translation"], &[0.0, 0.0, 0.0]);
+    let q = floats(&node["rotation"], &[0.0, 0.0, 0.0, 1.0]);
+    let s = floats(&node["scale"], &[1.0, 1.0, 1.0]);
+    Mat4::translation(Vec3::new(t[0], t[1], t[2]))
+        * Mat4::rotation_quaternion(q[0], q[1], q[2], q[3])
+        * Mat4::scale(s[0], s[1], s[2])
+}
+
+/// The JSON and binary chunks of a .glb file
+fn split_glb(data: &[u8]) -> io::Result<(&[u8], Option<&[u8]>)> {
+    let word = |at: usize| {
+        data.get(at..at + 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize)
+            .ok_or_else(|| invalid_gltf("truncated .glb file"))
+    };
+    if word(4)? != 2 {
+        return Err(invalid_gltf("only glTF 2.0 is supported"));
+    }
+
+    let length = word(8)?.min(data.len());
+    let mut offset = 12;
+    let mut json = None;
+    let mut bin = None;
+    while offset + 8 <= length {
+        let chunk_length = word(offset)?;
+        let chunk = data
+            .get(offset + 8..offset + 8 + chunk_length)
+            .ok_or_else(|| invalid_gltf("truncated .glb file"))?;
+        match &data[offset + 4..offset + 8] {
+            b"JSON" if json.is_none() => json = Some(chunk),
+            b"BIN\0" if bin.is_none() => bin = Some(chunk),
+            _ => {}
+        }
+        offset += 8 + chunk_length;
+    }
+    let json = json.ok_or_else(|| invalid_gltf(".glb file without JSON"))?;
+    Ok((json, bin))
+}
+
+/// The contents of a base64 data URI, or of a file relative to dir
+fn load_uri(uri: &str, dir: &Path) -> io::Result<Vec<u8>> {
+    if uri.starts_with("data:") {
+        let start = uri
+            .find(";base64,")
+            .ok_or_else(|| invalid_gltf("data URIs must be base64"))?;
+        return decode_base64(&uri[start + 8..]).ok_or_else(|| invalid_gltf("bad base64 data"));
+    }
+
+    // URIs escape spaces and other characters as %XX
+    let bytes = uri.as_bytes();
+    let mut path = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) if bytes[i] == b'%' => {
+                path.push(byte);
+                i += 3;
+            }
+            _ => {
+                path.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    fs::read(dir.join(String::from_utf8_lossy(&path).as_ref()))
+}
+
+fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(text.len() * 3 / 4);
+    let mut bits = 0u32;
+    let mut count = 0;
+    for c in text.bytes() {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            b'=' => break,
+            _ => return None,
+        };
+        bits = (bits << 6 | u32::from(value)) & 0xffff;
+        count += 6;
+        if count >= 8 {
+            count -= 8;
+            bytes.push((bits >> count) as u8);
+        }
+    }
+    Some(bytes)
+}
+
+fn vec3(v: &[f64; 4]) -> Vec3 {
+    Vec3::new(v[0] as f32, v[1] as f32, v[2] as f32)
+}
+
+fn gltf_index(value: &Value) -> Option<usize> {
+    value.as_u64().map(|index| index as usize)
+}
+
+/// The numbers of a JSON array, or default if it isn't one of the same length
+fn floats(value: &Value, default: &[f32]) -> Vec<f32> {
+    match value.as_array() {
+        Some(values) if values.len() == default.len() => values
+            .iter()
+            .map(|v| v.as_f64().unwrap_or(0.0) as f32)
+            .collect(),
+        _ => default.to_vec(),
+    }
+}
+
+/// The rgb part of an rgb or rgba JSON array, or a gray of value
+fn color(value: &Value, default: f32) -> Vec3 {
+    let values: Vec<f32> = value
+        .as_array()
+        .map_or(&[][..], |values| values)
+        .iter()
+        .map(|v| v.as_f64().unwrap_or(0.0) as f32)
+        .collect();
+    if values.len() >= 3 {
+        Vec3::new(values[0], values[1], values[2])
+    } else {
+        Vec3::new(default, default, default)
+    }
+}
+
+fn invalid_gltf(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A triangle from (-1, -1) to (1, -1) to (0, 1) in the xy plane
+    const TRIANGLE: [f32; 9] = [-1.0, -1.0, 0.0, 1.0, -1.0, 0.0, 0.0, 1.0, 0.0];
+
+    #[test]
+    fn glb_meshes_and_cameras_are_placed_by_their_nodes() {
+        let mut bin = Vec::new();
+        for value in &TRIANGLE {
+            bin.extend_from_slice(&value.to_le_bytes());
+        }
+        for index in &[0u16, 1, 2, 0] {
+            bin.extend_from_slice(&index.to_le_bytes());
+        }
+
+        let mut json = br#"{
+            "asset": {"version": "2.0"},
+            "scene": 0,
+            "scenes": [{"nodes": [0, 2, 3]}],
+            "nodes": [
+                {"children": [1], "translation": [0, 0, -5]},
+                {"mesh": 0, "scale": [2, 2, 2]},
+                {"camera": 0, "translation": [0, 1, 0]},
+                {"mesh": 0, "translation": [10, 0, -5]}
+            ],
+            "cameras": [{"type": "perspective", "perspective": {"yfov": 0.7, "znear": 0.1}}],
+            "meshes": [{"primitives": [{"attributes": {"POSITION": 0}, "indices": 1}]}],
+            "accessors": [
+                {"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3"},
+                {"bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR"}
+            ],
+            "bufferViews": [
+                {"buffer": 0, "byteLength": 36},
+                {"buffer": 0, "byteOffset": 36, "byteLength": 6}
+            ],
+            "buffers": [{"byteLength": 44}]
+        }"#
+        .to_vec();
+        // Chunks are padded to four bytes
+        let padding = (4 - json.len() % 4) % 4;
+        json.extend(vec![b' '; padding]);
+
+        let mut glb = b"glTF".to_vec();
+        glb.extend_from_slice(&2u32.to_le_bytes());
+        glb.extend_from_slice(&((28 + json.len() + bin.len()) as u32).to_le_bytes());
+        glb.extend_from_slice(&(json.len() as u32).to_le_bytes());
+        glb.extend_from_slice(b"JSON");
+        glb.extend_from_slice(&json);
+        glb.extend_from_slice(&(bin.len() as u32).to_le_bytes());
+        glb.extend_from_slice(b"BIN\0");
+        glb.extend_from_slice(&bin);
+
+        let scene = decode_gltf(&glb, Path::new(""), 20, 10, 1).unwrap();
+        let bbox = scene.world.bounding_box().unwrap();
+        assert!((bbox.min.x + 2.0).abs() < 1e-3 && (bbox.max.y - 2.0).abs() < 1e-3);
+        assert!((bbox.min.z + 5.0).abs() < 1e-3 && (bbox.max.x - 11.0).abs() < 1e-3);
+
+        let ray = scene.cam.get_ray(0.5, 0.5);
+        assert!((ray.origin - Vec3::new(0.0, 1.0, 0.0)).length() < 1e-5);
+        let rec = scene.world.hit(&ray, 0.001, f32::MAX).unwrap();
+        assert!((rec.t * ray.direction.length() - 5.0).abs() < 1e-3);
+        // Without a material the triangle is light gray
+        assert!((rec.material.albedo(&rec).x - 0.8).abs() < 1e-5);
+    }
+
+    #[test]
+    fn data_uris_and_materials_without_a_camera() {
+        let json = br#"{
+            "asset": {"version": "2.0"},
+            "nodes": [{"mesh": 0, "rotation": [0, 1, 0, 0]}],
+            "materials": [{"pbrMetallicRoughness": {"baseColorFactor": [1, 0.5, 0, 1], "roughnessFactor": 0.2}}],
+            "meshes": [{"primitives": [{"attributes": {"POSITION": 0}, "material": 0}]}],
+            "accessors": [{"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3"}],
+            "bufferViews": [{"buffer": 0, "byteLength": 36}],
+            "buffers": [{
+                "byteLength": 36,
+                "uri": "data:application/octet-stream;base64,AACAvwAAgL8AAAAAAACAPwAAgL8AAAAAAAAAAAAAgD8AAAAA"
+            }]
+        }"#;
+        let scene = decode_gltf(json, Path::new(""), 10, 10, 1).unwrap();
+
+        // The camera frames the triangle, which the rotation turned away from it
+        let ray = scene.cam.get_ray(0.5, 0.5);
+        let rec = scene.world.hit(&ray, 0.001, f32::MAX).unwrap();
+        assert!(rec.normal.z < -0.99);
+        match rec.material {
+            Material::Metal { albedo, fuzz } => {
+                assert!((albedo.y - 0.5).abs() < 1e-5 && (fuzz - 0.2).abs() < 1e-5);
+            }
+            _ => panic!("metallic materials should be metal"),
+        }
+
+        assert_eq!(decode_base64("aGk=").unwrap(), b"hi");
+        assert!(decode_gltf(b"{}", Path::new(""), 10, 10, 1).is_err());
+    }
+
+    #[test]
+    fn hostile_accessors_are_errors() {
+        let gltf = |accessor: &str, view: &str| {
+            let json = r#"{
+                "asset": {"version": "2.0"},
+                "nodes": [{"mesh": 0}],
+                "meshes": [{"primitives": [{"attributes": {"POSITION": 0}}]}],
+                "accessors": [ACCESSOR],
+                "bufferViews": [VIEW],
+                "buffers": [{
+                    "byteLength": 36,
+                    "uri": "data:application/octet-stream;base64,AACAvwAAgL8AAAAAAACAPwAAgL8AAAAAAAAAAAAAgD8AAAAA"
+                }]
+            }"#
+            .replace("ACCESSOR", accessor)
+            .replace("VIEW", view);
+            decode_gltf(json.as_bytes(), Path::new(""), 10, 10, 1)
+        };
+        let view = r#"{"buffer": 0, "byteLength": 36}"#;
+        let accessor = r#"{"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3"}"#;
+        assert!(gltf(accessor, view).is_ok());
+
+        let huge_offset = r#"{"bufferView": 0, "byteOffset": 18446744073709551615,
+            "componentType": 5126, "count": 3, "type": "VEC3"}"#;
+        assert!(gltf(huge_offset, view).is_err());
+        let huge_count = r#"{"bufferView": 0, "componentType": 5126,
+            "count": 1000000000000000, "type": "VEC3"}"#;
+        assert!(gltf(huge_count, view).is_err());
+        let unbacked = r#"{"componentType": 5126, "count": 1000000000000000, "type": "VEC3"}"#;
+        assert!(gltf(unbacked, view).is_err());
+    }
+}
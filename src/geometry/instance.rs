@@ -0,0 +1,169 @@
+use super::super::{light::LightBounds, material::Material, tracer::*};
+use super::rect::surface_light_bounds;
+use super::translation::{Transformed, Translation};
+use std::sync::Arc;
+
+/// One placement of a prototype hitable that any number of instances share,
+/// so copies of a mesh cost a transform each rather than their geometry
+#[derive(Clone)]
+pub struct Instance {
+    transformed: Transformed,
+    emissive: bool,
+    material: Option<Arc<Material>>,
+}
+
+impl Instance {
+    /// Place prototype by transform
+    pub fn new(prototype: Arc<dyn Hitable>, transform: Transform) -> Instance {
+        Instance {
+            emissive: prototype.light_bounds().is_some(),
+            transformed: Transformed::new(Box::new(prototype), transform),
+            material: None,
+        }
+    }
+
+    /// Draw the instance with material in place of the prototype's own.
+    /// Instances can share one material too.
+    pub fn with_material(self, material: Arc<Material>) -> Instance {
+        Instance {
+            material: Some(material),
+            ..self
+        }
+    }
+}
+
+impl Hitable for Instance {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let mut rec = self.transformed.hit(r, t_min, t_max)?;
+        if let Some(material) = &self.material {
+            rec.material = material;
+        }
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        self.transformed.bounding_box()
+    }
+
+    fn box_clone(&self) -> BoxHitable {
+        Box::new(self.clone())
+    }
+
+    fn sample(&self, origin: Vec3, u: (f32, f32)) -> Option<Vec3> {
+        self.transformed.sample(origin, u)
+    }
+
+    fn pdf(&self, origin: Vec3, direction: Vec3) -> f32 {
+        self.transformed.pdf(origin, direction)
+    }
+
+    /// The whole instance is one light when it gives off any, so the lights
+    /// share the prototype too
+    fn lights(&self) -> Vec<BoxHitable> {
+        let emissive = match &self.material {
+            Some(material) => material.is_emissive(),
+            None => self.emissive,
+        };
+        if emissive {
+            vec![self.box_clone()]
+        } else {
+            Vec::new()
+        }
+    }
+
+    fn area(&self) -> f32 {
+        self.transformed.area()
+    }
+
+    fn transformed_area(&self, transform: &Transform) -> f32 {
+        self.transformed.transformed_area(transform)
+    }
+
+    fn light_bounds(&self) -> Option<LightBounds> {
+        match &self.material {
+            Some(material) => {
+                // Normals of the prototype could point anywhere
+                let mut bounds = surface_light_bounds(self, material, Vec3::new(0.0, 1.0, 0.0))?;
+                bounds.cos_theta_o = -1.0;
+                Some(bounds)
+            }
+            None => self.transformed.light_bounds(),
+        }
+    }
+}
+
+impl Translation for Instance {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::Cuboid;
+    use crate::material::{diffuse_light, lambertion};
+
+    #[test]
+    fn instances_share_their_prototype() {
+        let prototype: Arc<dyn Hitable> = Arc::new(Cuboid::new(
+            Vec3::new(-1.0, -1.0, -1.0),
+            Vec3::new(1.0, 1.0, 1.0),
+            lambertion(0.5, 0.5, 0.5),
+        ));
+        let light = Arc::new(diffuse_light(4.0, 4.0, 4.0));
+        let list: Vec<BoxHitable> = (0..100)
+            .map(|i| {
+                let offset = Mat4::translation(Vec3::new(3.0 * i as f32, 0.0, 0.0));
+                let instance =
+                    Instance::new(prototype.clone(), Transform::try_new(offset).unwrap());
+                if i % 2 == 0 {
+                    Box::new(instance.with_material(light.clone())) as BoxHitable
+                } else {
+                    instance.box_clone()
+                }
+            })
+            .collect();
+        let world = BVHNode::new(list);
+        assert_eq!(Arc::strong_count(&prototype), 101);
+        assert_eq!(Arc::strong_count(&light), 51);
+        assert_eq!(world.lights().len(), 50);
+
+        let ray = Ray {
+            origin: Vec3::new(6.0, 5.0, 0.0),
+            direction: Vec3::new(0.0, -1.0, 0.0),
+        };
+        let rec = world.hit(&ray, 0.001, f32::MAX).unwrap();
+        assert!((rec.p.y - 1.0).abs() < 1e-4);
+        assert!(rec.material.is_emissive());
+        let ray = Ray {
+            origin: Vec3::new(3.0, 5.0, 0.0),
+            ..ray
+        };
+        assert!(!world
+            .hit(&ray, 0.001, f32::MAX)
+            .unwrap()
+            .material
+            .is_emissive());
+    }
+
+    #[test]
+    fn instances_of_lights_share_their_prototype() {
+        let prototype: Arc<dyn Hitable> = Arc::new(Cuboid::new(
+            Vec3::new(-1.0, -1.0, -1.0),
+            Vec3::new(1.0, 1.0, 1.0),
+            diffuse_light(4.0, 4.0, 4.0),
+        ));
+        let list: Vec<BoxHitable> = (0..100)
+            .map(|i| {
+                let offset = Mat4::translation(Vec3::new(3.0 * i as f32, 0.0, 0.0));
+                Instance::new(prototype.clone(), Transform::try_new(offset).unwrap()).box_clone()
+            })
+            .collect();
+        let world = BVHNode::new(list);
+        assert_eq!(Arc::strong_count(&prototype), 101);
+
+        // One light per instance, each sharing the prototype
+        let lights = world.lights();
+        assert_eq!(lights.len(), 100);
+        assert_eq!(Arc::strong_count(&prototype), 201);
+        let bounds = lights[0].light_bounds().unwrap();
+        assert!(bounds.power > 0.0 && bounds.cos_theta_o <= -1.0);
+    }
+}
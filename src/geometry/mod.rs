@@ -1,11 +1,23 @@
 pub mod constant_medium;
 pub mod cuboid;
+pub mod gltf;
+pub mod instance;
+pub mod obj;
+pub mod ply;
 pub mod rect;
 pub mod sphere;
+pub mod stl;
 pub mod translation;
+pub mod triangle_mesh;
 
 pub use constant_medium::*;
 pub use cuboid::*;
+pub use gltf::*;
+pub use instance::*;
+pub use obj::*;
+pub use ply::*;
 pub use rect::*;
 pub use sphere::*;
+pub use stl::*;
 pub use translation::*;
+pub use triangle_mesh::*;
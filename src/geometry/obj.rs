@@ -0,0 +1,384 @@
+use super::super::material::{self, Material};
+use super::super::texture::{BoxTexture, ConstantTexture, ImageTexture};
+use super::super::tracer::*;
+use super::triangle_mesh::{MeshBuffers, TriangleMesh};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Load a Wavefront .obj file, along with the .mtl files it references, as
+/// one mesh per group and material
+pub fn read_obj(path: &str) -> io::Result<Vec<TriangleMesh>> {
+    let source = fs::read_to_string(path)?;
+    let dir = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+
+    let mut materials = HashMap::new();
+    for line in source.lines() {
+        let mut tokens = line.split_whitespace();
+        if tokens.next() == Some("mtllib") {
+            for file in tokens {
+                materials.extend(read_mtl(&dir.join(file))?);
+            }
+        }
+    }
+    parse_obj(&source, &materials)
+}
+
+/// Parse the text of an .obj file, looking up `usemtl` names in materials.
+/// Faces without a known material get a light gray Lambertian.
+pub fn parse_obj(
+    source: &str,
+    materials: &HashMap<String, Material>,
+) -> io::Result<Vec<TriangleMesh>> {
+    let mut positions: Vec<Vec3> = Vec::new();
+    let mut normals: Vec<Vec3> = Vec::new();
+    let mut uvs: Vec<(f32, f32)> = Vec::new();
+
+    let mut builders: Vec<MeshBuilder> = Vec::new();
+    let mut builder_index: HashMap<(String, String), usize> = HashMap::new();
+    let mut group = String::new();
+    let mut material_name = String::new();
+
+    for (number, line) in source.lines().enumerate() {
+        let error = |message: &str| invalid_obj(&format!("line {}: {}", number + 1, message));
+        let line = line.split('#').next().unwrap_or("");
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+        let rest: Vec<&str> = tokens.collect();
+
+        match keyword {
+            "v" => {
+                let v = parse_floats(&rest, 3).ok_or_else(|| error("bad vertex"))?;
+                positions.push(Vec3::new(v[0], v[1], v[2]));
+            }
+            "vn" => {
+                let n = parse_floats(&rest, 3).ok_or_else(|| error("bad normal"))?;
+                normals.push(Vec3::new(n[0], n[1], n[2]));
+            }
+            "vt" => {
+                let t = parse_floats(&rest, 1).ok_or_else(|| error("bad uv"))?;
+                uvs.push((t[0], t.get(1).cloned().unwrap_or(0.0)));
+            }
+            "g" | "o" => group = rest.join(" "),
+            "usemtl" => material_name = rest.join(" "),
+            "f" => {
+                if rest.len() < 3 {
+                    return Err(error("faces need at least three vertices"));
+                }
+                let mut corners = Vec::with_capacity(rest.len());
+                for corner in &rest {
+                    let counts = (positions.len(), uvs.len(), normals.len());
+                    corners.push(parse_corner(corner, counts).ok_or_else(|| error("bad face"))?);
+                }
+
+                let key = (group.clone(), material_name.clone());
+                let index = *builder_index.entry(key).or_insert_with(|| {
+                    builders.push(MeshBuilder::new(material_name.clone()));
+                    builders.len() - 1
+                });
+                let builder = &mut builders[index];
+                let vertices: Vec<usize> = corners
+                    .iter()
+                    .map(|corner| builder.vertex(*corner, &positions, &uvs, &normals))
+                    .collect();
+                // Polygons are split into a fan of triangles
+                for i in 1..vertices.len() - 1 {
+                    builder
+                        .indices
+                        .push([vertices[0], vertices[i], vertices[i + 1]]);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(builders
+        .into_iter()
+        .map(|builder| {
+            let material = materials
+                .get(&builder.material)
+                .cloned()
+                .unwrap_or_else(|| material::lambertion(0.8, 0.8, 0.8));
+            builder.build(material)
+        })
+        .collect())
+}
+
+/// Load the materials of an .mtl file by name
+pub fn read_mtl(path: &Path) -> io::Result<HashMap<String, Material>> {
+    let source = fs::read_to_string(path)?;
+    parse_mtl(&source, path.parent().unwrap_or_else(|| Path::new("")))
+}
+
+/// Parse the text of an .mtl file, with texture paths relative to dir.
+/// Emissive materials (`Ke`) become lights, transparent ones (`d`, `Tr` or
+/// a glass `illum`) dielectrics with `Ni` as their index, mirrors (`illum 3`
+/// or a black `Kd` with a `Ks`) metals with `Ns` setting the fuzz, and the
+/// rest are Lambertian with `Kd` or `map_Kd` as their color.
+pub fn parse_mtl(source: &str, dir: &Path) -> io::Result<HashMap<String, Material>> {
+    let mut entries: Vec<(String, MtlEntry)> = Vec::new();
+    for (number, line) in source.lines().enumerate() {
+        let error = |message: &str| invalid_obj(&format!("line {}: {}", number + 1, message));
+        let line = line.split('#').next().unwrap_or("");
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+        let rest: Vec<&str> = tokens.collect();
+
+        if keyword == "newmtl" {
+            entries.push((rest.join(" "), MtlEntry::default()));
+            continue;
+        }
+        let entry = match entries.last_mut() {
+            Some((_, entry)) => entry,
+            None => continue,
+        };
+        let color = || {
+            parse_floats(&rest, 3)
+                .map(|c| Vec3::new(c[0], c[1], c[2]))
+                .ok_or_else(|| error("bad color"))
+        };
+        let float = || {
+            parse_floats(&rest, 1)
+                .map(|f| f[0])
+                .ok_or_else(|| error("bad number"))
+        };
+
+        match keyword {
+            "Kd" => entry.diffuse = color()?,
+            "Ks" => entry.specular = color()?,
+            "Ke" => entry.emission = color()?,
+            "Ns" => entry.shininess = float()?,
+            "Ni" => entry.index = float()?,
+            "d" => entry.dissolve = float()?,
+            "Tr" => entry.dissolve = 1.0 - float()?,
+            "illum" => entry.illum = float()? as u32,
+            // Options come before the file name, which is last
+            "map_Kd" => entry.diffuse_map = rest.last().map(|file| file.to_string()),
+            _ => {}
+        }
+    }
+
+    let mut materials = HashMap::new();
+    for (name, entry) in entries {
+        materials.insert(name, entry.material(dir)?);
+    }
+    Ok(materials)
+}
+
+/// Material settings read from an .mtl file
+struct MtlEntry {
+    diffuse: Vec3,
+    specular: Vec3,
+    emission: Vec3,
+    shininess: f32,
+    index: f32,
+    dissolve: f32,
+    illum: u32,
+    diffuse_map: Option<String>,
+}
+
+impl Default for MtlEntry {
+    fn default() -> MtlEntry {
+        MtlEntry {
+            diffuse: Vec3::new(0.8, 0.8, 0.8),
+            specular: Vec3::new(0.0, 0.0, 0.0),
+            emission: Vec3::new(0.0, 0.0, 0.0),
+            shininess: 0.0,
+            index: 1.0,
+            dissolve: 1.0,
+            illum: 2,
+            diffuse_map: None,
+        }
+    }
+}
+
+impl MtlEntry {
+    fn material(&self, dir: &Path) -> io::Result<Material> {
+        if luminance(self.emission) > 0.0 {
+            let e = self.emission;
+            return Ok(material::diffuse_light(e.x, e.y, e.z));
+        }
+
+        if self.dissolve < 1.0 || [4, 6, 7, 9].contains(&self.illum) {
+            let ref_idx = if self.index > 1.0 { self.index } else { 1.5 };
+            return Ok(material::dielectric(ref_idx));
+        }
+
+        let is_mirror =
+            self.illum == 3 || (luminance(self.diffuse) <= 0.0 && luminance(self.specular) > 0.0);
+        if is_mirror {
+            // Phong exponents map to roughly this much spread
+            let fuzz = (2.0 / (self.shininess + 2.0)).sqrt();
+            return Ok(material::metal(self.specular, fuzz));
+        }
+
+        let albedo = match &self.diffuse_map {
+            Some(file) => {
+                let image = image::open(dir.join(file))
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?
+                    .to_rgb();
+                let (nx, ny) = image.dimensions();
+                Box::new(ImageTexture {
+                    image: image.into_raw(),
+                    nx,
+                    ny,
+                }) as BoxTexture
+            }
+            None => Box::new(ConstantTexture::new(
+                self.diffuse.x,
+                self.diffuse.y,
+                self.diffuse.z,
+            )),
+        };
+        Ok(Material::Lambertion { albedo })
+    }
+}
+
+/// Indices into the file's positions, uvs and normals for one face corner
+type Corner = (usize, Option<usize>, Option<usize>);
+
+/// Collects the faces of one group and material, giving every distinct
+/// corner its own vertex
+struct MeshBuilder {
+    material: String,
+    vertices: HashMap<Corner, usize>,
+    positions: Vec<Vec3>,
+    uvs: Vec<Option<(f32, f32)>>,
+    normals: Vec<Option<Vec3>>,
+    indices: Vec<[usize; 3]>,
+}
+
+impl MeshBuilder {
+    fn new(material: String) -> MeshBuilder {
+        MeshBuilder {
+            material,
+            vertices: HashMap::new(),
+            positions: Vec::new(),
+            uvs: Vec::new(),
+            normals: Vec::new(),
+            indices: Vec::new(),
+        }
+    }
+
+    fn vertex(
+        &mut self,
+        corner: Corner,
+        positions: &[Vec3],
+        uvs: &[(f32, f32)],
+        normals: &[Vec3],
+    ) -> usize {
+        if let Some(index) = self.vertices.get(&corner) {
+            return *index;
+        }
+        let (position, uv, normal) = corner;
+        self.positions.push(positions[position]);
+        self.uvs.push(uv.map(|uv| uvs[uv]));
+        self.normals.push(normal.map(|normal| normals[normal]));
+        self.vertices.insert(corner, self.positions.len() - 1);
+        self.positions.len() - 1
+    }
+
+    /// Normals and uvs are only kept when every vertex has one
+    fn build(self, material: Material) -> TriangleMesh {
+        let normals = self.normals.into_iter().collect::<Option<Vec<_>>>();
+        let uvs = self.uvs.into_iter().collect::<Option<Vec<_>>>();
+        let buffers = MeshBuffers {
+            positions: self.positions,
+            normals: normals.unwrap_or_default(),
+            uvs: uvs.unwrap_or_default(),
+            indices: self.indices,
+            ..MeshBuffers::default()
+        };
+        TriangleMesh::new(buffers, material)
+    }
+}
+
+/// Parse the first count numbers of tokens, None if there are fewer or they
+/// aren't numbers
+fn parse_floats(tokens: &[&str], count: usize) -> Option<Vec<f32>> {
+    if tokens.len() < count {
+        return None;
+    }
+    tokens.iter().map(|token| token.parse().ok()).collect()
+}
+
+/// Parse a face corner like `1`, `1/2`, `1//3` or `1/2/3` into zero based
+/// indices. Indices are one based, or relative to the end when negative.
+fn parse_corner(corner: &str, (positions, uvs, normals): (usize, usize, usize)) -> Option<Corner> {
+    let resolve = |index: &str, count: usize| -> Option<usize> {
+        let index: i64 = index.parse().ok()?;
+        let resolved = if index < 0 {
+            count as i64 + index
+        } else {
+            index - 1
+        };
+        if resolved >= 0 && (resolved as usize) < count {
+            Some(resolved as usize)
+        } else {
+            None
+        }
+    };
+
+    let mut parts = corner.split('/');
+    let position = resolve(parts.next()?, positions)?;
+    let uv = match parts.next() {
+        Some(uv) if !uv.is_empty() => Some(resolve(uv, uvs)?),
+        _ => None,
+    };
+    let normal = match parts.next() {
+        Some(normal) if !normal.is_empty() => Some(resolve(normal, normals)?),
+        _ => None,
+    };
+    Some((position, uv, normal))
+}
+
+fn invalid_obj(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn groups_and_materials_split_meshes() {
+        let mtl = "newmtl red\nKd 0.8 0.1 0.1\n\nnewmtl glass\nd 0.2\nNi 1.4\n\
+                   newmtl lamp\nKe 5 5 5\n";
+        let materials = parse_mtl(mtl, Path::new("")).unwrap();
+        assert!(matches!(materials["glass"], Material::Dielectric { ref_idx } if ref_idx == 1.4));
+        assert!(materials["lamp"].is_emissive());
+
+        let obj = "# a quad and a triangle\n\
+                   v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n\
+                   vt 0 0\nvt 1 0\nvt 1 1\nvt 0 1\nvn 0 0 1\n\
+                   g quad\nusemtl red\nf 1/1/1 2/2/1 3/3/1 4/4/1\n\
+                   g tri\nusemtl glass\nf -4//1 -3//1 -1//1\n";
+        let meshes = parse_obj(obj, &materials).unwrap();
+        assert_eq!(meshes.len(), 2);
+        assert_eq!(meshes[0].triangle_count(), 2);
+        assert_eq!(meshes[1].triangle_count(), 1);
+        assert!(matches!(meshes[1].material(), Material::Dielectric { .. }));
+
+        let ray = Ray {
+            origin: Vec3::new(0.75, 0.5, 1.0),
+            direction: Vec3::new(0.0, 0.0, -1.0),
+        };
+        let rec = meshes[0].hit(&ray, 0.001, f32::MAX).unwrap();
+        assert!((rec.u - 0.75).abs() < 1e-5 && (rec.v - 0.5).abs() < 1e-5);
+        assert!((rec.normal.z - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn bad_indices_are_errors() {
+        let materials = HashMap::new();
+        assert!(parse_obj("v 0 0 0\nv 1 0 0\nf 1 2 3\n", &materials).is_err());
+        assert!(parse_obj("v 0 0 0\nf 1 1\n", &materials).is_err());
+    }
+}
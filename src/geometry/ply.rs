@@ -0,0 +1,413 @@
+use super::super::material::{self, Material};
+use super::super::texture::VertexColorTexture;
+use super::super::tracer::*;
+use super::triangle_mesh::{MeshBuffers, TriangleMesh};
+use std::fs;
+use std::io;
+
+/// Load an ASCII or binary .ply mesh. Meshes with vertex colors get a
+/// Lambertian material that uses them as albedo, others a light gray one.
+pub fn read_ply(path: &str) -> io::Result<TriangleMesh> {
+    decode_ply(&fs::read(path)?)
+}
+
+/// Decode the contents of a .ply file. Vertices can have normals, uvs and
+/// colors, and faces with more than three vertices are split into
+/// triangles. Other elements and properties are skipped.
+pub fn decode_ply(data: &[u8]) -> io::Result<TriangleMesh> {
+    let (format, elements, body) = parse_ply_header(data)?;
+    let mut reader = PlyReader {
+        data: &data[body..],
+        pos: 0,
+        format,
+        tokens: Vec::new(),
+    };
+    if format == PlyFormat::Ascii {
+        let text = std::str::from_utf8(reader.data).map_err(|_| invalid_ply("not text"))?;
+        reader.tokens = text.split_whitespace().rev().collect();
+    }
+
+    let mut buffers = MeshBuffers::default();
+    for element in &elements {
+        match element.name.as_str() {
+            "vertex" => read_vertices(&mut reader, element, &mut buffers)?,
+            "face" => read_faces(&mut reader, element, &mut buffers)?,
+            _ => {
+                for _ in 0..element.count {
+                    for property in &element.properties {
+                        reader.property(property)?;
+                    }
+                }
+            }
+        }
+    }
+
+    let vertex_count = buffers.positions.len();
+    if buffers.indices.iter().flatten().any(|i| *i >= vertex_count) {
+        return Err(invalid_ply("face refers to a missing vertex"));
+    }
+
+    let material = if buffers.colors.is_empty() {
+        material::lambertion(0.8, 0.8, 0.8)
+    } else {
+        Material::Lambertion {
+            albedo: Box::new(VertexColorTexture),
+        }
+    };
+    Ok(TriangleMesh::new(buffers, material))
+}
+
+fn read_vertices(
+    reader: &mut PlyReader,
+    element: &PlyElement,
+    buffers: &mut MeshBuffers,
+) -> io::Result<()> {
+    let find = |names: &[&str]| {
+        element
+            .properties
+            .iter()
+            .position(|property| property.list.is_none() && names.contains(&property.name.as_str()))
+    };
+    let position = [find(&["x"]), find(&["y"]), find(&["z"])];
+    let normal = [find(&["nx"]), find(&["ny"]), find(&["nz"])];
+    let uv = [
+        find(&["u", "s", "texture_u", "texture_s"]),
+        find(&["v", "t", "texture_v", "texture_t"]),
+    ];
+    let color = [
+        find(&["red", "diffuse_red"]),
+        find(&["green", "diffuse_green"]),
+        find(&["blue", "diffuse_blue"]),
+    ];
+    let (x, y, z) = match position {
+        [Some(x), Some(y), Some(z)] => (x, y, z),
+        _ => return Err(invalid_ply("vertices need x, y and z")),
+    };
+
+    let mut values = vec![0.0; element.properties.len()];
+    for _ in 0..element.count {
+        for (value, property) in values.iter_mut().zip(&element.properties) {
+            *value = reader.property(property)?;
+        }
+
+        let vec3 = |x: usize, y: usize, z: usize| {
+            Vec3::new(values[x] as f32, values[y] as f32, values[z] as f32)
+        };
+        buffers.positions.push(vec3(x, y, z));
+        if let [Some(nx), Some(ny), Some(nz)] = normal {
+            buffers.normals.push(vec3(nx, ny, nz));
+        }
+        if let [Some(u), Some(v)] = uv {
+            buffers.uvs.push((values[u] as f32, values[v] as f32));
+        }
+        if let [Some(r), Some(g), Some(b)] = color {
+            // Integer colors run up to their type's largest value
+            let scale = |i: usize| (values[i] / element.properties[i].kind.max_value()) as f32;
+            buffers.colors.push(Vec3::new(scale(r), scale(g), scale(b)));
+        }
+    }
+    Ok(())
+}
+
+fn read_faces(
+    reader: &mut PlyReader,
+    element: &PlyElement,
+    buffers: &mut MeshBuffers,
+) -> io::Result<()> {
+    let indices = element.properties.iter().position(|property| {
+        property.list.is_some()
+            && (property.name == "vertex_indices" || property.name == "vertex_index")
+    });
+    let indices = indices.ok_or_else(|| invalid_ply("faces need vertex_indices"))?;
+
+    let mut face = Vec::new();
+    for _ in 0..element.count {
+        for (i, property) in element.properties.iter().enumerate() {
+            if i != indices {
+                reader.property(property)?;
+                continue;
+            }
+
+            let count = reader.read(property.list.unwrap_or(PlyType::U8))? as usize;
+            face.clear();
+            for _ in 0..count {
+                face.push(reader.read(property.kind)? as usize);
+            }
+            // Polygons are split into a fan of triangles
+            for i in 1..face.len().saturating_sub(1) {
+                buffers.indices.push([face[0], face[i], face[i + 1]]);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum PlyFormat {
+    Ascii,
+    LittleEndian,
+    BigEndian,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum PlyType {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl PlyType {
+    fn parse(name: &str) -> Option<PlyType> {
+        Some(match name {
+            "char" | "int8" => PlyType::I8,
+            "uchar" | "uint8" => PlyType::U8,
+            "short" | "int16" => PlyType::I16,
+            "ushort" | "uint16" => PlyType::U16,
+            "int" | "int32" => PlyType::I32,
+            "uint" | "uint32" => PlyType::U32,
+            "float" | "float32" => PlyType::F32,
+            "double" | "float64" => PlyType::F64,
+            _ => return None,
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            PlyType::I8 | PlyType::U8 => 1,
+            PlyType::I16 | PlyType::U16 => 2,
+            PlyType::I32 | PlyType::U32 | PlyType::F32 => 4,
+            PlyType::F64 => 8,
+        }
+    }
+
+    /// Value that stands for full intensity in colors of this type
+    fn max_value(self) -> f64 {
+        match self {
+            PlyType::I8 => 127.0,
+            PlyType::U8 => 255.0,
+            PlyType::I16 => 32767.0,
+            PlyType::U16 => 65535.0,
+            PlyType::I32 => 2_147_483_647.0,
+            PlyType::U32 => 4_294_967_295.0,
+            PlyType::F32 | PlyType::F64 => 1.0,
+        }
+    }
+}
+
+struct PlyProperty {
+    name: String,
+    kind: PlyType,
+    /// Type of the length of list properties, whose items are of kind
+    list: Option<PlyType>,
+}
+
+struct PlyElement {
+    name: String,
+    count: usize,
+    properties: Vec<PlyProperty>,
+}
+
+/// Returns the format, the elements and where their data starts
+fn parse_ply_header(data: &[u8]) -> io::Result<(PlyFormat, Vec<PlyElement>, usize)> {
+    let mut format = None;
+    let mut elements: Vec<PlyElement> = Vec::new();
+    let mut pos = 0;
+    let mut first = true;
+    loop {
+        let end = data[pos..]
+            .iter()
+            .position(|byte| *byte == b'\n')
+            .ok_or_else(|| invalid_ply("header has no end_header"))?;
+        let line = String::from_utf8_lossy(&data[pos..pos + end]);
+        pos += end + 1;
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+
+        if first {
+            if tokens != ["ply"] {
+                return Err(invalid_ply("not a PLY file"));
+            }
+            first = false;
+            continue;
+        }
+
+        match tokens.as_slice() {
+            ["format", name, _] => {
+                format = Some(match *name {
+                    "ascii" => PlyFormat::Ascii,
+                    "binary_little_endian" => PlyFormat::LittleEndian,
+                    "binary_big_endian" => PlyFormat::BigEndian,
+                    _ => return Err(invalid_ply("unknown format")),
+                })
+            }
+            ["element", name, count] => elements.push(PlyElement {
+                name: name.to_string(),
+                count: count
+                    .parse()
+                    .map_err(|_| invalid_ply("bad element count"))?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count, kind, name] => {
+                let element = elements
+                    .last_mut()
+                    .ok_or_else(|| invalid_ply("property before element"))?;
+                element.properties.push(PlyProperty {
+                    name: name.to_string(),
+                    kind: PlyType::parse(kind).ok_or_else(|| invalid_ply("unknown type"))?,
+                    list: Some(PlyType::parse(count).ok_or_else(|| invalid_ply("unknown type"))?),
+                });
+            }
+            ["property", kind, name] => {
+                let element = elements
+                    .last_mut()
+                    .ok_or_else(|| invalid_ply("property before element"))?;
+                element.properties.push(PlyProperty {
+                    name: name.to_string(),
+                    kind: PlyType::parse(kind).ok_or_else(|| invalid_ply("unknown type"))?,
+                    list: None,
+                });
+            }
+            ["end_header"] => break,
+            _ => {}
+        }
+    }
+
+    let format = format.ok_or_else(|| invalid_ply("header has no format"))?;
+    Ok((format, elements, pos))
+}
+
+/// Reads values from the data after the header, either as text tokens or
+/// binary numbers
+struct PlyReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    format: PlyFormat,
+    /// Remaining tokens of ASCII files, last one first
+    tokens: Vec<&'a str>,
+}
+
+impl<'a> PlyReader<'a> {
+    fn read(&mut self, kind: PlyType) -> io::Result<f64> {
+        if self.format == PlyFormat::Ascii {
+            let token = self
+                .tokens
+                .pop()
+                .ok_or_else(|| invalid_ply("unexpected end of data"))?;
+            return token.parse().map_err(|_| invalid_ply("bad number"));
+        }
+
+        let size = kind.size();
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + size)
+            .ok_or_else(|| invalid_ply("unexpected end of data"))?;
+        self.pos += size;
+
+        let mut b = [0u8; 8];
+        b[..size].copy_from_slice(bytes);
+        if self.format == PlyFormat::BigEndian {
+            b[..size].reverse();
+        }
+        Ok(match kind {
+            PlyType::I8 => f64::from(b[0] as i8),
+            PlyType::U8 => f64::from(b[0]),
+            PlyType::I16 => f64::from(i16::from_le_bytes([b[0], b[1]])),
+            PlyType::U16 => f64::from(u16::from_le_bytes([b[0], b[1]])),
+            PlyType::I32 => f64::from(i32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+            PlyType::U32 => f64::from(u32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+            PlyType::F32 => f64::from(f32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+            PlyType::F64 => f64::from_le_bytes(b),
+        })
+    }
+
+    /// Read a property, returning 0 for lists, whose items are skipped
+    fn property(&mut self, property: &PlyProperty) -> io::Result<f64> {
+        match property.list {
+            Some(count) => {
+                let count = self.read(count)? as usize;
+                for _ in 0..count {
+                    self.read(property.kind)?;
+                }
+                Ok(0.0)
+            }
+            None => self.read(property.kind),
+        }
+    }
+}
+
+fn invalid_ply(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hit_color(mesh: &TriangleMesh, x: f32, y: f32) -> Vec3 {
+        let ray = Ray {
+            origin: Vec3::new(x, y, 1.0),
+            direction: Vec3::new(0.0, 0.0, -1.0),
+        };
+        let rec = mesh.hit(&ray, 0.001, f32::MAX).unwrap();
+        rec.material.albedo(&rec)
+    }
+
+    #[test]
+    fn ascii_vertex_colors_become_the_albedo() {
+        let ply = "ply\nformat ascii 1.0\ncomment made by hand\n\
+                   element vertex 4\nproperty float x\nproperty float y\nproperty float z\n\
+                   property uchar red\nproperty uchar green\nproperty uchar blue\n\
+                   element face 1\nproperty list uchar int vertex_indices\n\
+                   element edge 1\nproperty int vertex1\nproperty int vertex2\nend_header\n\
+                   0 0 0 255 0 0\n1 0 0 255 0 0\n1 1 0 0 0 255\n0 1 0 0 0 255\n\
+                   4 0 1 2 3\n0 1\n";
+        let mesh = decode_ply(ply.as_bytes()).unwrap();
+        assert_eq!(mesh.triangle_count(), 2);
+
+        let bottom = hit_color(&mesh, 0.5, 0.01);
+        let top = hit_color(&mesh, 0.5, 0.99);
+        assert!(bottom.x > 0.95 && bottom.z < 0.05);
+        assert!(top.z > 0.95 && top.x < 0.05);
+    }
+
+    #[test]
+    fn binary_files_of_either_byte_order_decode() {
+        for (format, big_endian) in &[("binary_little_endian", false), ("binary_big_endian", true)]
+        {
+            let mut data = format!(
+                "ply\nformat {} 1.0\nelement vertex 3\nproperty float x\nproperty float y\n\
+                 property float z\nelement face 1\nproperty list uchar uint vertex_index\n\
+                 end_header\n",
+                format
+            )
+            .into_bytes();
+            let positions = [0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0];
+            for value in &positions {
+                let bytes = if *big_endian {
+                    value.to_be_bytes()
+                } else {
+                    value.to_le_bytes()
+                };
+                data.extend_from_slice(&bytes);
+            }
+            data.push(3);
+            for index in 0..3u32 {
+                let bytes = if *big_endian {
+                    index.to_be_bytes()
+                } else {
+                    index.to_le_bytes()
+                };
+                data.extend_from_slice(&bytes);
+            }
+
+            let mesh = decode_ply(&data).unwrap();
+            assert_eq!(mesh.triangle_count(), 1);
+            assert_eq!(mesh.buffers().positions[1].x, 1.0);
+            assert_eq!(hit_color(&mesh, 0.2, 0.2).x, 0.8);
+        }
+    }
+}
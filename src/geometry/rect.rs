@@ -1,5 +1,6 @@
-use super::super::{material::Material, tracer::*};
+use super::super::{light::LightBounds, material::Material, tracer::*};
 use super::translation::Translation;
+use std::f32::consts::PI;
 
 #[derive(Clone)]
 pub struct XYRect {
@@ -30,6 +31,8 @@ impl Hitable for XYRect {
             v,
             p: r.point_at_parameter(t),
             material: &self.material,
+            object_id: 0,
+            vertex_color: None,
             normal: Vec3::new(0.0, 0.0, 1.0),
         })
     }
@@ -44,6 +47,42 @@ impl Hitable for XYRect {
     fn box_clone(&self) -> BoxHitable {
         Box::new(self.clone())
     }
+
+    fn sample(&self, origin: Vec3, u: (f32, f32)) -> Option<Vec3> {
+        Some(
+            Vec3::new(
+                self.x0 + u.0 * (self.x1 - self.x0),
+                self.y0 + u.1 * (self.y1 - self.y0),
+                self.k,
+            ) - origin,
+        )
+    }
+
+    fn pdf(&self, origin: Vec3, direction: Vec3) -> f32 {
+        surface_pdf(self, Vec3::new(0.0, 0.0, 1.0), origin, direction)
+    }
+
+    fn lights(&self) -> Vec<BoxHitable> {
+        if self.material.is_emissive() {
+            vec![self.box_clone()]
+        } else {
+            Vec::new()
+        }
+    }
+
+    fn area(&self) -> f32 {
+        (self.x1 - self.x0) * (self.y1 - self.y0)
+    }
+
+    fn transformed_area(&self, transform: &Transform) -> f32 {
+        let edge0 = transform.transform_vector(Vec3::new(self.x1 - self.x0, 0.0, 0.0));
+        let edge1 = transform.transform_vector(Vec3::new(0.0, self.y1 - self.y0, 0.0));
+        edge0.cross(&edge1).length()
+    }
+
+    fn light_bounds(&self) -> Option<LightBounds> {
+        surface_light_bounds(self, &self.material, Vec3::new(0.0, 0.0, 1.0))
+    }
 }
 
 impl Translation for XYRect {}
@@ -77,6 +116,8 @@ impl Hitable for XZRect {
             v,
             p: r.point_at_parameter(t),
             material: &self.material,
+            object_id: 0,
+            vertex_color: None,
             normal: Vec3::new(0.0, 1.0, 0.0),
         })
     }
@@ -91,6 +132,42 @@ impl Hitable for XZRect {
     fn box_clone(&self) -> BoxHitable {
         Box::new(self.clone())
     }
+
+    fn sample(&self, origin: Vec3, u: (f32, f32)) -> Option<Vec3> {
+        Some(
+            Vec3::new(
+                self.x0 + u.0 * (self.x1 - self.x0),
+                self.k,
+                self.z0 + u.1 * (self.z1 - self.z0),
+            ) - origin,
+        )
+    }
+
+    fn pdf(&self, origin: Vec3, direction: Vec3) -> f32 {
+        surface_pdf(self, Vec3::new(0.0, 1.0, 0.0), origin, direction)
+    }
+
+    fn lights(&self) -> Vec<BoxHitable> {
+        if self.material.is_emissive() {
+            vec![self.box_clone()]
+        } else {
+            Vec::new()
+        }
+    }
+
+    fn area(&self) -> f32 {
+        (self.x1 - self.x0) * (self.z1 - self.z0)
+    }
+
+    fn transformed_area(&self, transform: &Transform) -> f32 {
+        let edge0 = transform.transform_vector(Vec3::new(self.x1 - self.x0, 0.0, 0.0));
+        let edge1 = transform.transform_vector(Vec3::new(0.0, 0.0, self.z1 - self.z0));
+        edge0.cross(&edge1).length()
+    }
+
+    fn light_bounds(&self) -> Option<LightBounds> {
+        surface_light_bounds(self, &self.material, Vec3::new(0.0, 1.0, 0.0))
+    }
 }
 
 impl Translation for XZRect {}
@@ -126,6 +203,8 @@ impl Hitable for YZRect {
             v,
             p: r.point_at_parameter(t),
             material: &self.material,
+            object_id: 0,
+            vertex_color: None,
             normal: Vec3::new(1.0, 0.0, 0.0),
         })
     }
@@ -140,6 +219,85 @@ impl Hitable for YZRect {
     fn box_clone(&self) -> BoxHitable {
         Box::new(self.clone())
     }
+
+    fn sample(&self, origin: Vec3, u: (f32, f32)) -> Option<Vec3> {
+        Some(
+            Vec3::new(
+                self.k,
+                self.y0 + u.0 * (self.y1 - self.y0),
+                self.z0 + u.1 * (self.z1 - self.z0),
+            ) - origin,
+        )
+    }
+
+    fn pdf(&self, origin: Vec3, direction: Vec3) -> f32 {
+        surface_pdf(self, Vec3::new(1.0, 0.0, 0.0), origin, direction)
+    }
+
+    fn lights(&self) -> Vec<BoxHitable> {
+        if self.material.is_emissive() {
+            vec![self.box_clone()]
+        } else {
+            Vec::new()
+        }
+    }
+
+    fn area(&self) -> f32 {
+        (self.y1 - self.y0) * (self.z1 - self.z0)
+    }
+
+    fn transformed_area(&self, transform: &Transform) -> f32 {
+        let edge0 = transform.transform_vector(Vec3::new(0.0, self.y1 - self.y0, 0.0));
+        let edge1 = transform.transform_vector(Vec3::new(0.0, 0.0, self.z1 - self.z0));
+        edge0.cross(&edge1).length()
+    }
+
+    fn light_bounds(&self) -> Option<LightBounds> {
+        surface_light_bounds(self, &self.material, Vec3::new(1.0, 0.0, 0.0))
+    }
 }
 
 impl Translation for YZRect {}
+
+/// Light bounds of a flat surface whose normal points along axis
+pub(super) fn surface_light_bounds(
+    surface: &impl Hitable,
+    material: &Material,
+    axis: Vec3,
+) -> Option<LightBounds> {
+    if !material.is_emissive() {
+        return None;
+    }
+    let bbox = surface.bounding_box()?;
+    let center = 0.5 * (bbox.min + bbox.max);
+    let radiance = luminance(material.emission(0.5, 0.5, center));
+    let two_sided = material.is_two_sided();
+    let sides = if two_sided { 2.0 } else { 1.0 };
+    Some(LightBounds {
+        bbox,
+        power: sides * PI * surface.area() * radiance,
+        axis,
+        cos_theta_o: 1.0,
+        cos_theta_e: 0.0,
+        two_sided,
+    })
+}
+
+/// Solid angle density of direction, as seen from origin, when points are
+/// picked uniformly over a flat surface with the given geometric normal
+pub(super) fn surface_pdf(
+    surface: &impl Hitable,
+    normal: Vec3,
+    origin: Vec3,
+    direction: Vec3,
+) -> f32 {
+    let ray = Ray { origin, direction };
+    let rec = match surface.hit(&ray, 0.001, f32::MAX) {
+        Some(rec) => rec,
+        None => return 0.0,
+    };
+
+    let distance_squared = rec.t * rec.t * direction.squared_length();
+    let cosine = (direction.dot(&normal) / (direction.length() * normal.length())).abs();
+    distance_squared / (cosine * surface.area()).max(1e-8)
+}
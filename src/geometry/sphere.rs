@@ -1,4 +1,5 @@
-use super::super::{material::Material, tracer::*};
+use super::super::sampler::{orthonormal_basis, uniform_sphere};
+use super::super::{light::LightBounds, material::Material, tracer::*};
 use super::translation::Translation;
 use std::f32::consts::PI;
 use std::ops::Deref;
@@ -39,6 +40,8 @@ impl Hitable for Sphere {
                     v,
                     p,
                     material: &self.material,
+                    object_id: 0,
+                    vertex_color: None,
                     normal,
                 })
             };
@@ -65,6 +68,74 @@ impl Hitable for Sphere {
     fn box_clone(&self) -> BoxHitable {
         Box::new(self.clone())
     }
+
+    fn sample(&self, origin: Vec3, u: (f32, f32)) -> Option<Vec3> {
+        let to_center = self.center - origin;
+        let distance_squared = to_center.squared_length();
+        let radius_squared = self.radius * self.radius;
+
+        // From inside, every point of the sphere is visible, pick one uniformly
+        if distance_squared <= radius_squared {
+            return Some(self.center + self.radius * uniform_sphere(u) - origin);
+        }
+
+        // From outside, pick a direction in the cone the sphere fills
+        let cos_theta_max = (1.0 - radius_squared / distance_squared).sqrt();
+        let cos_theta = 1.0 + u.0 * (cos_theta_max - 1.0);
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * u.1;
+        let w = to_center.unit_vector();
+        let (a, b) = orthonormal_basis(w);
+        Some(sin_theta * phi.cos() * a + sin_theta * phi.sin() * b + cos_theta * w)
+    }
+
+    fn pdf(&self, origin: Vec3, direction: Vec3) -> f32 {
+        let ray = Ray { origin, direction };
+        let rec = match self.hit(&ray, 0.001, f32::MAX) {
+            Some(rec) => rec,
+            None => return 0.0,
+        };
+
+        let distance_squared = (self.center - origin).squared_length();
+        let radius_squared = self.radius * self.radius;
+        if distance_squared <= radius_squared {
+            let to_point = rec.p - origin;
+            let cosine = rec.normal.dot(&to_point.unit_vector()).abs();
+            let area = 4.0 * PI * radius_squared;
+            return to_point.squared_length() / (cosine * area).max(1e-8);
+        }
+
+        let cos_theta_max = (1.0 - radius_squared / distance_squared).sqrt();
+        1.0 / (2.0 * PI * (1.0 - cos_theta_max)).max(1e-8)
+    }
+
+    fn lights(&self) -> Vec<BoxHitable> {
+        if self.material.is_emissive() {
+            vec![self.box_clone()]
+        } else {
+            Vec::new()
+        }
+    }
+
+    fn area(&self) -> f32 {
+        4.0 * PI * self.radius * self.radius
+    }
+
+    fn light_bounds(&self) -> Option<LightBounds> {
+        if !self.material.is_emissive() {
+            return None;
+        }
+        let radiance = luminance(self.material.emission(0.5, 0.5, self.center));
+        let sides = if self.material.is_two_sided() {
+            2.0
+        } else {
+            1.0
+        };
+        Some(LightBounds::omnidirectional(
+            self.bounding_box()?,
+            sides * PI * self.area() * radiance,
+        ))
+    }
 }
 
 impl Hitable for Box<Sphere> {
@@ -79,6 +150,26 @@ impl Hitable for Box<Sphere> {
     fn box_clone(&self) -> BoxHitable {
         self.deref().box_clone()
     }
+
+    fn sample(&self, origin: Vec3, u: (f32, f32)) -> Option<Vec3> {
+        self.deref().sample(origin, u)
+    }
+
+    fn pdf(&self, origin: Vec3, direction: Vec3) -> f32 {
+        self.deref().pdf(origin, direction)
+    }
+
+    fn lights(&self) -> Vec<BoxHitable> {
+        self.deref().lights()
+    }
+
+    fn area(&self) -> f32 {
+        self.deref().area()
+    }
+
+    fn light_bounds(&self) -> Option<LightBounds> {
+        self.deref().light_bounds()
+    }
 }
 
 impl Translation for Sphere {}
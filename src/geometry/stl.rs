@@ -0,0 +1,133 @@
+use super::super::material;
+use super::super::tracer::*;
+use super::triangle_mesh::{MeshBuffers, TriangleMesh};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+
+/// Load an ASCII or binary .stl mesh with a light gray Lambertian material
+pub fn read_stl(path: &str) -> io::Result<TriangleMesh> {
+    decode_stl(&fs::read(path)?)
+}
+
+/// Decode the contents of a .stl file. Corners at the same position are
+/// merged into one vertex. The stored facet normals are ignored in favor of
+/// the winding order, which the format requires to agree with them.
+pub fn decode_stl(data: &[u8]) -> io::Result<TriangleMesh> {
+    let mut corners = Vec::new();
+
+    // Binary files may start with "solid" too, their size gives them away
+    let binary_count = data
+        .get(80..84)
+        .map(|count| u32::from_le_bytes([count[0], count[1], count[2], count[3]]) as usize);
+    match binary_count {
+        Some(count) if data.len() == 84 + 50 * count => {
+            for triangle in data[84..].chunks(50) {
+                // Each triangle is a normal, three corners and two spare bytes
+                for corner in 1..4 {
+                    let float = |i: usize| {
+                        let start = 12 * corner + 4 * i;
+                        let b = &triangle[start..start + 4];
+                        f32::from_le_bytes([b[0], b[1], b[2], b[3]])
+                    };
+                    corners.push(Vec3::new(float(0), float(1), float(2)));
+                }
+            }
+        }
+        _ => {
+            let text = std::str::from_utf8(data).map_err(|_| invalid_stl("not an STL file"))?;
+            if !text.trim_start().starts_with("solid") {
+                return Err(invalid_stl("not an STL file"));
+            }
+            let mut tokens = text.split_whitespace();
+            while let Some(token) = tokens.next() {
+                if token != "vertex" {
+                    continue;
+                }
+                let mut float = || -> io::Result<f32> {
+                    let token = tokens.next().ok_or_else(|| invalid_stl("bad vertex"))?;
+                    token.parse().map_err(|_| invalid_stl("bad vertex"))
+                };
+                corners.push(Vec3::new(float()?, float()?, float()?));
+            }
+            if corners.len() % 3 != 0 {
+                return Err(invalid_stl("facets need three vertices"));
+            }
+        }
+    }
+
+    let mut buffers = MeshBuffers::default();
+    let mut vertices: HashMap<[u32; 3], usize> = HashMap::new();
+    let mut indices = Vec::with_capacity(corners.len());
+    for corner in corners {
+        let key = [corner.x.to_bits(), corner.y.to_bits(), corner.z.to_bits()];
+        let positions = &mut buffers.positions;
+        let index = *vertices.entry(key).or_insert_with(|| {
+            positions.push(corner);
+            positions.len() - 1
+        });
+        indices.push(index);
+    }
+    buffers.indices = indices
+        .chunks(3)
+        .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+        .collect();
+
+    Ok(TriangleMesh::new(
+        buffers,
+        material::lambertion(0.8, 0.8, 0.8),
+    ))
+}
+
+fn invalid_stl(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ascii_and_binary_files_share_corners() {
+        let square = [
+            [0.0f32, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [0.0, 1.0, 0.0],
+        ];
+
+        // Headers starting with solid mustn't be mistaken for ASCII
+        let mut binary = b"solid square".to_vec();
+        binary.resize(80, 0);
+        binary.extend_from_slice(&2u32.to_le_bytes());
+        for triangle in square.chunks(3) {
+            binary.extend_from_slice(&[0; 12]);
+            for corner in triangle {
+                for value in corner {
+                    binary.extend_from_slice(&value.to_le_bytes());
+                }
+            }
+            binary.extend_from_slice(&[0; 2]);
+        }
+
+        let mut ascii = String::from("solid square\n");
+        for triangle in square.chunks(3) {
+            ascii += "facet normal 0 0 1\nouter loop\n";
+            for corner in triangle {
+                ascii += &format!("vertex {} {} {}\n", corner[0], corner[1], corner[2]);
+            }
+            ascii += "endloop\nendfacet\n";
+        }
+        ascii += "endsolid square\n";
+
+        for data in &[binary, ascii.into_bytes()] {
+            let mesh = decode_stl(data).unwrap();
+            assert_eq!(mesh.triangle_count(), 2);
+            assert_eq!(mesh.buffers().positions.len(), 4);
+            assert!((mesh.area() - 1.0).abs() < 1e-5);
+        }
+        assert!(decode_stl(b"not a mesh").is_err());
+    }
+}
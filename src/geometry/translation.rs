@@ -1,3 +1,4 @@
+use super::super::light::LightBounds;
 use super::super::tracer::*;
 use std::f32::{consts::PI, MAX as F32MAX};
 
@@ -22,6 +23,36 @@ impl Hitable for FlipNormals {
     fn box_clone(&self) -> BoxHitable {
         Box::new(self.clone())
     }
+
+    fn sample(&self, origin: Vec3, u: (f32, f32)) -> Option<Vec3> {
+        self.hitable.sample(origin, u)
+    }
+
+    fn pdf(&self, origin: Vec3, direction: Vec3) -> f32 {
+        self.hitable.pdf(origin, direction)
+    }
+
+    fn lights(&self) -> Vec<BoxHitable> {
+        self.hitable
+            .lights()
+            .into_iter()
+            .map(|light| Box::new(flip_normals(light)) as BoxHitable)
+            .collect()
+    }
+
+    fn area(&self) -> f32 {
+        self.hitable.area()
+    }
+
+    fn transformed_area(&self, transform: &Transform) -> f32 {
+        self.hitable.transformed_area(transform)
+    }
+
+    fn light_bounds(&self) -> Option<LightBounds> {
+        let mut bounds = self.hitable.light_bounds()?;
+        bounds.axis = -bounds.axis;
+        Some(bounds)
+    }
 }
 
 impl Translation for FlipNormals {}
@@ -67,6 +98,44 @@ impl Hitable for Shift {
     fn box_clone(&self) -> BoxHitable {
         Box::new(self.clone())
     }
+
+    fn sample(&self, origin: Vec3, u: (f32, f32)) -> Option<Vec3> {
+        self.hitable.sample(origin - self.offset, u)
+    }
+
+    fn pdf(&self, origin: Vec3, direction: Vec3) -> f32 {
+        self.hitable.pdf(origin - self.offset, direction)
+    }
+
+    fn lights(&self) -> Vec<BoxHitable> {
+        self.hitable
+            .lights()
+            .into_iter()
+            .map(|light| {
+                Box::new(Shift {
+                    hitable: light,
+                    offset: self.offset,
+                }) as BoxHitable
+            })
+            .collect()
+    }
+
+    fn area(&self) -> f32 {
+        self.hitable.area()
+    }
+
+    fn transformed_area(&self, transform: &Transform) -> f32 {
+        self.hitable.transformed_area(transform)
+    }
+
+    fn light_bounds(&self) -> Option<LightBounds> {
+        let mut bounds = self.hitable.light_bounds()?;
+        bounds.bbox = BoundingBox {
+            min: bounds.bbox.min + self.offset,
+            max: bounds.bbox.max + self.offset,
+        };
+        Some(bounds)
+    }
 }
 
 impl Translation for Shift {}
@@ -127,6 +196,24 @@ impl YRotation {
             cos_theta,
         }
     }
+
+    /// Rotate a world space vector into the wrapped hitable's space
+    fn to_object(&self, v: Vec3) -> Vec3 {
+        Vec3::new(
+            self.cos_theta * v.x - self.sin_theta * v.z,
+            v.y,
+            self.sin_theta * v.x + self.cos_theta * v.z,
+        )
+    }
+
+    /// Rotate a vector in the wrapped hitable's space into world space
+    fn to_world(&self, v: Vec3) -> Vec3 {
+        Vec3::new(
+            self.cos_theta * v.x + self.sin_theta * v.z,
+            v.y,
+            -self.sin_theta * v.x + self.cos_theta * v.z,
+        )
+    }
 }
 
 impl Hitable for YRotation {
@@ -155,10 +242,221 @@ impl Hitable for YRotation {
     fn box_clone(&self) -> BoxHitable {
         Box::new(self.clone())
     }
+
+    fn sample(&self, origin: Vec3, u: (f32, f32)) -> Option<Vec3> {
+        let direction = self.hitable.sample(self.to_object(origin), u)?;
+        Some(self.to_world(direction))
+    }
+
+    fn pdf(&self, origin: Vec3, direction: Vec3) -> f32 {
+        self.hitable
+            .pdf(self.to_object(origin), self.to_object(direction))
+    }
+
+    fn lights(&self) -> Vec<BoxHitable> {
+        self.hitable
+            .lights()
+            .into_iter()
+            .map(|light| {
+                Box::new(YRotation {
+                    bbox: light
+                        .bounding_box()
+                        .map(|bbox| Self::gen_bbox(bbox, self.cos_theta, self.sin_theta)),
+                    hitable: light,
+                    sin_theta: self.sin_theta,
+                    cos_theta: self.cos_theta,
+                }) as BoxHitable
+            })
+            .collect()
+    }
+
+    fn area(&self) -> f32 {
+        self.hitable.area()
+    }
+
+    fn transformed_area(&self, transform: &Transform) -> f32 {
+        let rotation = Mat4 {
+            m: [
+                [self.cos_theta, 0.0, self.sin_theta, 0.0],
+                [0.0, 1.0, 0.0, 0.0],
+                [-self.sin_theta, 0.0, self.cos_theta, 0.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        };
+        self.hitable
+            .transformed_area(&(*transform * Transform::try_new(rotation).unwrap()))
+    }
+
+    fn light_bounds(&self) -> Option<LightBounds> {
+        let mut bounds = self.hitable.light_bounds()?;
+        bounds.bbox = Self::gen_bbox(bounds.bbox, self.cos_theta, self.sin_theta);
+        bounds.axis = self.to_world(bounds.axis);
+        Some(bounds)
+    }
 }
 
 impl Translation for YRotation {}
 
+/// A hitable moved by any invertible affine transform. Rays are taken into
+/// the wrapped hitable's space and hits brought back out.
+#[derive(Clone)]
+pub struct Transformed {
+    hitable: BoxHitable,
+    transform: Transform,
+    bbox: Option<BoundingBox>,
+}
+
+impl Transformed {
+    pub fn new(hitable: BoxHitable, transform: Transform) -> Transformed {
+        let bbox = hitable
+            .bounding_box()
+            .map(|bbox| transform.transform_bounding_box(&bbox));
+        Transformed {
+            hitable,
+            transform,
+            bbox,
+        }
+    }
+}
+
+impl Hitable for Transformed {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let inverse = self.transform.inverse();
+        // The direction isn't normalized, so t is the same in both spaces
+        let object_ray = Ray {
+            origin: inverse.transform_point(r.origin),
+            direction: inverse.transform_vector(r.direction),
+        };
+        let rec = self.hitable.hit(&object_ray, t_min, t_max)?;
+        Some(HitRecord {
+            p: self.transform.transform_point(rec.p),
+            normal: self.transform.transform_normal(rec.normal).unit_vector(),
+            ..rec
+        })
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        self.bbox
+    }
+
+    fn box_clone(&self) -> BoxHitable {
+        Box::new(self.clone())
+    }
+
+    fn sample(&self, origin: Vec3, u: (f32, f32)) -> Option<Vec3> {
+        let object_origin = self.transform.inverse().transform_point(origin);
+        let direction = self.hitable.sample(object_origin, u)?;
+        Some(self.transform.transform_vector(direction))
+    }
+
+    fn pdf(&self, origin: Vec3, direction: Vec3) -> f32 {
+        let inverse = self.transform.inverse();
+        let object_direction = inverse.transform_vector(direction).unit_vector();
+        let pdf = self
+            .hitable
+            .pdf(inverse.transform_point(origin), object_direction);
+        // Directions are squeezed or spread out by the transform, which
+        // changes their density by this Jacobian
+        let stretch = self.transform.transform_vector(object_direction).length();
+        pdf * stretch.powi(3) / self.transform.determinant().abs()
+    }
+
+    fn lights(&self) -> Vec<BoxHitable> {
+        self.hitable
+            .lights()
+            .into_iter()
+            .map(|light| Box::new(Transformed::new(light, self.transform)) as BoxHitable)
+            .collect()
+    }
+
+    fn area(&self) -> f32 {
+        self.hitable.transformed_area(&self.transform)
+    }
+
+    fn transformed_area(&self, transform: &Transform) -> f32 {
+        self.hitable
+            .transformed_area(&(*transform * self.transform))
+    }
+
+    fn light_bounds(&self) -> Option<LightBounds> {
+        let mut bounds = self.hitable.light_bounds()?;
+        bounds.bbox = self.transform.transform_bounding_box(&bounds.bbox);
+        let area = self.hitable.area();
+        if area > 0.0 {
+            bounds.power *= self.area() / area;
+        }
+        bounds.axis = self.transform.transform_normal(bounds.axis).unit_vector();
+        // Stretching turns the normals by different amounts, so any of them
+        // could point anywhere
+        if self.transform.uniform_scale().is_none() {
+            bounds.cos_theta_o = -1.0;
+        }
+        Some(bounds)
+    }
+}
+
+impl Translation for Transformed {}
+
+/// Tags every hit on the wrapped hitable with an id for the object id AOV
+#[derive(Clone)]
+pub struct ObjectId {
+    hitable: BoxHitable,
+    id: u32,
+}
+
+impl Hitable for ObjectId {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let rec = self.hitable.hit(r, t_min, t_max)?;
+        Some(HitRecord {
+            object_id: self.id,
+            ..rec
+        })
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        self.hitable.bounding_box()
+    }
+
+    fn box_clone(&self) -> BoxHitable {
+        Box::new(self.clone())
+    }
+
+    fn sample(&self, origin: Vec3, u: (f32, f32)) -> Option<Vec3> {
+        self.hitable.sample(origin, u)
+    }
+
+    fn pdf(&self, origin: Vec3, direction: Vec3) -> f32 {
+        self.hitable.pdf(origin, direction)
+    }
+
+    fn lights(&self) -> Vec<BoxHitable> {
+        self.hitable
+            .lights()
+            .into_iter()
+            .map(|light| {
+                Box::new(ObjectId {
+                    hitable: light,
+                    id: self.id,
+                }) as BoxHitable
+            })
+            .collect()
+    }
+
+    fn area(&self) -> f32 {
+        self.hitable.area()
+    }
+
+    fn transformed_area(&self, transform: &Transform) -> f32 {
+        self.hitable.transformed_area(transform)
+    }
+
+    fn light_bounds(&self) -> Option<LightBounds> {
+        self.hitable.light_bounds()
+    }
+}
+
+impl Translation for ObjectId {}
+
 pub trait Translation: Hitable + Sized {
     fn shift(self, x: f32, y: f32, z: f32) -> Shift {
         Shift {
@@ -171,10 +469,44 @@ pub trait Translation: Hitable + Sized {
         YRotation::new(self.box_clone(), angle)
     }
 
+    /// Rotate about the x axis by angle in degrees
+    fn rotate_x(self, angle: f32) -> Transformed {
+        self.transform(Mat4::rotation_x(angle))
+    }
+
+    /// Rotate about the z axis by angle in degrees
+    fn rotate_z(self, angle: f32) -> Transformed {
+        self.transform(Mat4::rotation_z(angle))
+    }
+
+    /// Rotate about axis by angle in degrees
+    fn rotate_axis(self, axis: Vec3, angle: f32) -> Transformed {
+        self.transform(Mat4::rotation(axis, angle))
+    }
+
+    /// Scale by x, y and z along each axis, about the origin
+    fn scale(self, x: f32, y: f32, z: f32) -> Transformed {
+        self.transform(Mat4::scale(x, y, z))
+    }
+
+    /// Move by any affine transform. Panics if matrix can't be inverted.
+    fn transform(self, matrix: Mat4) -> Transformed {
+        let transform = Transform::try_new(matrix).expect("transform matrices must be invertible");
+        Transformed::new(self.box_clone(), transform)
+    }
+
     fn flip_normals(self) -> FlipNormals {
         flip_normals(self.box_clone())
     }
 
+    /// Tag hits on this hitable with id in the object id AOV
+    fn with_id(self, id: u32) -> ObjectId {
+        ObjectId {
+            hitable: self.box_clone(),
+            id,
+        }
+    }
+
     fn to_box(self) -> Box<Self> {
         Box::new(self)
     }
@@ -187,3 +519,89 @@ pub trait Translation: Hitable + Sized {
         list.push(self.to_box())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::{Sphere, XZRect};
+    use crate::material::diffuse_light;
+    use crate::sampler::uniform_sphere;
+
+    #[test]
+    fn stretched_spheres_keep_their_pdf_and_normals() {
+        let sphere = Sphere {
+            center: Vec3::new(0.0, 0.0, 0.0),
+            radius: 0.5,
+            material: diffuse_light(1.0, 1.0, 1.0),
+        };
+        let stretched = sphere
+            .scale(2.0, 1.0, 0.5)
+            .rotate_z(30.0)
+            .shift(0.5, 2.0, 0.0)
+            .transform(Mat4::identity());
+
+        let ray = Ray {
+            origin: Vec3::new(0.5, 2.0, 5.0),
+            direction: Vec3::new(0.0, 0.0, -1.0),
+        };
+        let rec = stretched.hit(&ray, 0.001, f32::MAX).unwrap();
+        assert!((rec.p.z - 0.25).abs() < 1e-4 && rec.normal.z > 0.999);
+        let bbox = stretched.bounding_box().unwrap();
+        assert!((bbox.max.z - 0.25).abs() < 1e-4);
+
+        let n = 400;
+        let p = Vec3::new(0.0, 0.0, 0.0);
+        let mut integral = 0.0;
+        for i in 0..n {
+            for j in 0..n {
+                let u = ((i as f32 + 0.5) / n as f32, (j as f32 + 0.5) / n as f32);
+                integral += stretched.pdf(p, uniform_sphere(u));
+            }
+        }
+        integral *= 4.0 * std::f32::consts::PI / (n * n) as f32;
+        assert!((integral - 1.0).abs() < 0.02, "{}", integral);
+
+        let direction = stretched.sample(p, (0.3, 0.7)).unwrap();
+        assert!(stretched
+            .hit(
+                &Ray {
+                    origin: p,
+                    direction
+                },
+                0.001,
+                f32::MAX
+            )
+            .is_some());
+    }
+
+    #[test]
+    fn stretched_rects_keep_their_exact_area() {
+        let rect = XZRect {
+            x0: 0.0,
+            x1: 1.0,
+            z0: 0.0,
+            z1: 1.0,
+            k: 0.0,
+            material: diffuse_light(1.0, 1.0, 1.0),
+        };
+        let power = rect.light_bounds().unwrap().power;
+        let stretched = rect.rotate_y(45.0).scale(2.0, 1.0, 1.0);
+        assert!(
+            (stretched.area() - 2.0).abs() < 1e-4,
+            "{}",
+            stretched.area()
+        );
+        let stretched_power = stretched.light_bounds().unwrap().power;
+        assert!((stretched_power - 2.0 * power).abs() < 1e-3 * power);
+
+        let tilted = stretched.rotate_x(30.0).scale(1.0, 3.0, 1.0);
+        let expected = Transform::try_new(
+            Mat4::scale(1.0, 3.0, 1.0) * Mat4::rotation_x(30.0) * Mat4::scale(2.0, 1.0, 1.0),
+        )
+        .unwrap();
+        let edge0 = expected.transform_vector(Vec3::new(0.5f32.sqrt(), 0.0, -(0.5f32.sqrt())));
+        let edge1 = expected.transform_vector(Vec3::new(0.5f32.sqrt(), 0.0, 0.5f32.sqrt()));
+        let area = edge0.cross(&edge1).length();
+        assert!((tilted.area() - area).abs() < 1e-4 * area);
+    }
+}
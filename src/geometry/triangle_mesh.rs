@@ -0,0 +1,389 @@
+use super::super::{light::LightBounds, material::Material, tracer::*};
+use super::rect::{surface_light_bounds, surface_pdf};
+use super::translation::Translation;
+use std::sync::Arc;
+
+/// Vertex buffers of a mesh, and the three vertices of every triangle.
+/// normals, uvs and colors are per vertex like positions. Any of them can be
+/// left empty, for flat shading, uvs from the barycentric coordinates or no
+/// vertex colors.
+#[derive(Clone, Default)]
+pub struct MeshBuffers {
+    pub positions: Vec<Vec3>,
+    pub normals: Vec<Vec3>,
+    pub uvs: Vec<(f32, f32)>,
+    /// Read by VertexColorTexture
+    pub colors: Vec<Vec3>,
+    pub indices: Vec<[usize; 3]>,
+}
+
+/// Buffers and material shared by every triangle of a mesh
+struct MeshData {
+    buffers: MeshBuffers,
+    material: Material,
+}
+
+/// Triangles sharing vertex buffers, with a BVH of their own. Triangles wound
+/// counter clockwise face the viewer.
+#[derive(Clone)]
+pub struct TriangleMesh {
+    data: Arc<MeshData>,
+    triangles: Option<BVHNode>,
+    /// Bounds of every triangle together, worked out once for emissive meshes
+    light_bounds: Option<LightBounds>,
+}
+
+impl TriangleMesh {
+    pub fn new(buffers: MeshBuffers, material: Material) -> TriangleMesh {
+        let data = Arc::new(MeshData { buffers, material });
+        let list = TriangleMesh::triangle_list(&data);
+        let triangles = if list.is_empty() {
+            None
+        } else {
+            Some(BVHNode::new(list))
+        };
+        let light_bounds = match &triangles {
+            Some(triangles) if data.material.is_emissive() => triangles.light_bounds(),
+            _ => None,
+        };
+        TriangleMesh {
+            data,
+            triangles,
+            light_bounds,
+        }
+    }
+
+    /// Number of triangles in the mesh
+    pub fn triangle_count(&self) -> usize {
+        self.data.buffers.indices.len()
+    }
+
+    pub fn buffers(&self) -> &MeshBuffers {
+        &self.data.buffers
+    }
+
+    pub fn material(&self) -> &Material {
+        &self.data.material
+    }
+
+    /// The same triangles with another material
+    pub fn with_material(self, material: Material) -> TriangleMesh {
+        // The triangles share the buffers, without them they can be moved
+        drop(self.triangles);
+        let buffers = match Arc::try_unwrap(self.data) {
+            Ok(data) => data.buffers,
+            Err(data) => data.buffers.clone(),
+        };
+        TriangleMesh::new(buffers, material)
+    }
+
+    fn triangle_list(data: &Arc<MeshData>) -> Vec<BoxHitable> {
+        (0..data.buffers.indices.len())
+            .map(|index| {
+                Box::new(Triangle {
+                    mesh: data.clone(),
+                    index,
+                }) as BoxHitable
+            })
+            .collect()
+    }
+}
+
+impl Hitable for TriangleMesh {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        self.triangles.as_ref()?.hit(r, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        self.triangles.as_ref()?.bounding_box()
+    }
+
+    fn box_clone(&self) -> BoxHitable {
+        Box::new(self.clone())
+    }
+
+    fn sample(&self, origin: Vec3, u: (f32, f32)) -> Option<Vec3> {
+        self.triangles.as_ref()?.sample(origin, u)
+    }
+
+    fn pdf(&self, origin: Vec3, direction: Vec3) -> f32 {
+        match &self.triangles {
+            Some(triangles) => triangles.pdf(origin, direction),
+            None => 0.0,
+        }
+    }
+
+    /// Every triangle is its own light
+    fn lights(&self) -> Vec<BoxHitable> {
+        if self.data.material.is_emissive() {
+            TriangleMesh::triangle_list(&self.data)
+        } else {
+            Vec::new()
+        }
+    }
+
+    fn area(&self) -> f32 {
+        match &self.triangles {
+            Some(triangles) => triangles.area(),
+            None => 0.0,
+        }
+    }
+
+    fn transformed_area(&self, transform: &Transform) -> f32 {
+        match &self.triangles {
+            Some(triangles) => triangles.transformed_area(transform),
+            None => 0.0,
+        }
+    }
+
+    fn light_bounds(&self) -> Option<LightBounds> {
+        self.light_bounds
+    }
+}
+
+impl Translation for TriangleMesh {}
+
+/// One triangle of a mesh
+#[derive(Clone)]
+struct Triangle {
+    mesh: Arc<MeshData>,
+    index: usize,
+}
+
+impl Triangle {
+    fn positions(&self) -> (Vec3, Vec3, Vec3) {
+        let [i0, i1, i2] = self.mesh.buffers.indices[self.index];
+        let positions = &self.mesh.buffers.positions;
+        (positions[i0], positions[i1], positions[i2])
+    }
+
+    /// Unit normal of the triangle's plane, facing the side it is wound
+    /// counter clockwise from
+    fn face_normal(&self) -> Vec3 {
+        let (p0, p1, p2) = self.positions();
+        (p1 - p0).cross(&(p2 - p0)).unit_vector()
+    }
+}
+
+impl Hitable for Triangle {
+    /// Möller-Trumbore intersection
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let (p0, p1, p2) = self.positions();
+        let edge1 = p1 - p0;
+        let edge2 = p2 - p0;
+        let pvec = r.direction.cross(&edge2);
+        let det = edge1.dot(&pvec);
+        if det.abs() < 1e-12 {
+            return None;
+        }
+
+        let inv_det = 1.0 / det;
+        let tvec = r.origin - p0;
+        let b1 = tvec.dot(&pvec) * inv_det;
+        if !(0.0..=1.0).contains(&b1) {
+            return None;
+        }
+        let qvec = tvec.cross(&edge1);
+        let b2 = r.direction.dot(&qvec) * inv_det;
+        if b2 < 0.0 || b1 + b2 > 1.0 {
+            return None;
+        }
+        let t = edge2.dot(&qvec) * inv_det;
+        if t <= t_min || t >= t_max {
+            return None;
+        }
+
+        let b0 = 1.0 - b1 - b2;
+        let buffers = &self.mesh.buffers;
+        let [i0, i1, i2] = buffers.indices[self.index];
+        // Lights don't scatter, so they keep the face normal, which decides
+        // the side they shine from
+        let mut normal = edge1.cross(&edge2).unit_vector();
+        if !buffers.normals.is_empty() && !self.mesh.material.is_emissive() {
+            let normals = &buffers.normals;
+            let shading = b0 * normals[i0] + b1 * normals[i1] + b2 * normals[i2];
+            if shading.squared_length() > 0.0 {
+                normal = shading.unit_vector();
+            }
+        }
+        let (u, v) = if buffers.uvs.is_empty() {
+            (b1, b2)
+        } else {
+            let uvs = &buffers.uvs;
+            (
+                b0 * uvs[i0].0 + b1 * uvs[i1].0 + b2 * uvs[i2].0,
+                b0 * uvs[i0].1 + b1 * uvs[i1].1 + b2 * uvs[i2].1,
+            )
+        };
+
+        let vertex_color = if buffers.colors.is_empty() {
+            None
+        } else {
+            let colors = &buffers.colors;
+            Some(b0 * colors[i0] + b1 * colors[i1] + b2 * colors[i2])
+        };
+
+        Some(HitRecord {
+            t,
+            u,
+            v,
+            p: r.point_at_parameter(t),
+            normal,
+            material: &self.mesh.material,
+            object_id: 0,
+            vertex_color,
+        })
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        let (p0, p1, p2) = self.positions();
+        // Padded so triangles lying in an axis plane still have some volume
+        let padding = Vec3::new(0.0001, 0.0001, 0.0001);
+        Some(BoundingBox {
+            min: Vec3::new(
+                p0.x.min(p1.x).min(p2.x),
+                p0.y.min(p1.y).min(p2.y),
+                p0.z.min(p1.z).min(p2.z),
+            ) - padding,
+            max: Vec3::new(
+                p0.x.max(p1.x).max(p2.x),
+                p0.y.max(p1.y).max(p2.y),
+                p0.z.max(p1.z).max(p2.z),
+            ) + padding,
+        })
+    }
+
+    fn box_clone(&self) -> BoxHitable {
+        Box::new(self.clone())
+    }
+
+    fn sample(&self, origin: Vec3, u: (f32, f32)) -> Option<Vec3> {
+        let (p0, p1, p2) = self.positions();
+        let root = u.0.sqrt();
+        let b0 = 1.0 - root;
+        let b1 = u.1 * root;
+        Some(b0 * p0 + b1 * p1 + (1.0 - b0 - b1) * p2 - origin)
+    }
+
+    fn pdf(&self, origin: Vec3, direction: Vec3) -> f32 {
+        surface_pdf(self, self.face_normal(), origin, direction)
+    }
+
+    fn lights(&self) -> Vec<BoxHitable> {
+        if self.mesh.material.is_emissive() {
+            vec![self.box_clone()]
+        } else {
+            Vec::new()
+        }
+    }
+
+    fn area(&self) -> f32 {
+        let (p0, p1, p2) = self.positions();
+        0.5 * (p1 - p0).cross(&(p2 - p0)).length()
+    }
+
+    fn transformed_area(&self, transform: &Transform) -> f32 {
+        let (p0, p1, p2) = self.positions();
+        let edge0 = transform.transform_vector(p1 - p0);
+        let edge1 = transform.transform_vector(p2 - p0);
+        0.5 * edge0.cross(&edge1).length()
+    }
+
+    fn light_bounds(&self) -> Option<LightBounds> {
+        surface_light_bounds(self, &self.mesh.material, self.face_normal())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::{diffuse_light, lambertion};
+    use crate::sampler::uniform_sphere;
+
+    #[test]
+    fn hits_interpolate_normals_and_uvs() {
+        let mesh = TriangleMesh::new(
+            MeshBuffers {
+                positions: vec![
+                    Vec3::new(0.0, 0.0, 0.0),
+                    Vec3::new(1.0, 0.0, 0.0),
+                    Vec3::new(0.0, 1.0, 0.0),
+                    Vec3::new(1.0, 1.0, 0.0),
+                ],
+                normals: vec![
+                    Vec3::new(0.0, 0.0, 1.0),
+                    Vec3::new(1.0, 0.0, 1.0).unit_vector(),
+                    Vec3::new(0.0, 0.0, 1.0),
+                    Vec3::new(1.0, 0.0, 1.0).unit_vector(),
+                ],
+                uvs: vec![(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0)],
+                indices: vec![[0, 1, 2], [1, 3, 2]],
+                ..MeshBuffers::default()
+            },
+            lambertion(0.5, 0.5, 0.5),
+        );
+        assert_eq!(mesh.triangle_count(), 2);
+        assert!((mesh.area() - 1.0).abs() < 1e-5);
+
+        let ray = Ray {
+            origin: Vec3::new(0.5, 0.25, 2.0),
+            direction: Vec3::new(0.0, 0.0, -1.0),
+        };
+        let rec = mesh.hit(&ray, 0.001, f32::MAX).unwrap();
+        assert!((rec.t - 2.0).abs() < 1e-5);
+        assert!((rec.u - 0.5).abs() < 1e-5 && (rec.v - 0.25).abs() < 1e-5);
+        assert!(rec.normal.x > 0.0 && rec.normal.z > 0.0);
+        assert!((rec.normal.length() - 1.0).abs() < 1e-5);
+
+        let miss = Ray {
+            origin: Vec3::new(1.5, 0.5, 2.0),
+            direction: Vec3::new(0.0, 0.0, -1.0),
+        };
+        assert!(mesh.hit(&miss, 0.001, f32::MAX).is_none());
+    }
+
+    #[test]
+    fn smooth_emissive_meshes_pdf_integrates_to_one() {
+        let mesh = TriangleMesh::new(
+            MeshBuffers {
+                positions: vec![
+                    Vec3::new(-1.0, 2.0, -1.0),
+                    Vec3::new(1.0, 2.0, -1.0),
+                    Vec3::new(-1.0, 2.0, 1.0),
+                    Vec3::new(1.0, 2.5, 1.0),
+                ],
+                // Shading normals far from the faces' own
+                normals: vec![
+                    Vec3::new(1.0, -1.0, 0.0).unit_vector(),
+                    Vec3::new(0.0, -1.0, 1.0).unit_vector(),
+                    Vec3::new(-1.0, -1.0, 0.0).unit_vector(),
+                    Vec3::new(0.0, -1.0, -1.0).unit_vector(),
+                ],
+                indices: vec![[0, 1, 2], [1, 3, 2]],
+                ..MeshBuffers::default()
+            },
+            diffuse_light(1.0, 1.0, 1.0),
+        );
+
+        let n = 400;
+        let p = Vec3::new(0.3, 0.0, 0.2);
+        let mut integral = 0.0;
+        for i in 0..n {
+            for j in 0..n {
+                let u = ((i as f32 + 0.5) / n as f32, (j as f32 + 0.5) / n as f32);
+                integral += mesh.pdf(p, uniform_sphere(u));
+            }
+        }
+        integral *= 4.0 * std::f32::consts::PI / (n * n) as f32;
+        assert!((integral - 1.0).abs() < 0.02, "{}", integral);
+
+        // The faces point down, towards p, so p sees their lit side
+        let direction = mesh.sample(p, (0.3, 0.7)).unwrap();
+        let ray = Ray {
+            origin: p,
+            direction,
+        };
+        let rec = mesh.hit(&ray, 0.001, f32::MAX).unwrap();
+        assert!(rec.material.emitted(&ray, &rec).x > 0.0);
+    }
+}
@@ -3,7 +3,10 @@ extern crate lazy_static;
 
 pub mod animation;
 pub mod geometry;
+pub mod light;
 pub mod material;
+pub mod output;
+pub mod sampler;
 pub mod texture;
 pub mod tracer;
 pub mod utils;
@@ -0,0 +1,92 @@
+use super::bounds::LightBounds;
+use super::lighting::{BoxLight, Light, LightSample};
+use crate::tracer::{BoxHitable, Hitable, Ray, Vec3};
+
+/// An emissive hitable, lit by its material's emitted light
+#[derive(Clone)]
+pub struct AreaLight {
+    pub hitable: BoxHitable,
+}
+
+impl Light for AreaLight {
+    fn sample(&self, p: Vec3, u: (f32, f32)) -> Option<LightSample> {
+        let direction = self.hitable.sample(p, u)?;
+        let ray = Ray {
+            origin: p,
+            direction,
+        };
+        let rec = self.hitable.hit(&ray, 0.001, f32::MAX)?;
+        let pdf = self.hitable.pdf(p, direction);
+        if pdf <= 0.0 {
+            return None;
+        }
+
+        Some(LightSample {
+            direction: direction.unit_vector(),
+            distance: rec.t * direction.length(),
+            radiance: rec.material.emitted(&ray, &rec),
+            pdf,
+            delta: false,
+        })
+    }
+
+    fn pdf(&self, p: Vec3, direction: Vec3) -> f32 {
+        self.hitable.pdf(p, direction)
+    }
+
+    fn bounds(&self) -> Option<LightBounds> {
+        self.hitable.light_bounds()
+    }
+
+    fn box_clone(&self) -> BoxLight {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::{Sphere, XZRect};
+    use crate::material::diffuse_light;
+    use crate::sampler::uniform_sphere;
+
+    #[test]
+    fn pdf_integrates_to_one_over_all_directions() {
+        let lights = vec![
+            AreaLight {
+                hitable: Box::new(XZRect {
+                    x0: -1.0,
+                    x1: 1.0,
+                    z0: -0.5,
+                    z1: 1.5,
+                    k: 2.0,
+                    material: diffuse_light(1.0, 1.0, 1.0),
+                }),
+            },
+            AreaLight {
+                hitable: Box::new(Sphere {
+                    center: Vec3::new(1.0, 2.0, 0.0),
+                    radius: 0.5,
+                    material: diffuse_light(1.0, 1.0, 1.0),
+                }),
+            },
+        ];
+
+        let n = 400;
+        let p = Vec3::new(0.0, 0.0, 0.0);
+        for light in &lights {
+            let mut integral = 0.0;
+            for i in 0..n {
+                for j in 0..n {
+                    let u = ((i as f32 + 0.5) / n as f32, (j as f32 + 0.5) / n as f32);
+                    integral += light.pdf(p, uniform_sphere(u));
+                }
+            }
+            integral *= 4.0 * std::f32::consts::PI / (n * n) as f32;
+            assert!((integral - 1.0).abs() < 0.02, "{}", integral);
+
+            let sample = light.sample(p, (0.3, 0.7)).unwrap();
+            assert!((sample.pdf - light.pdf(p, sample.direction)).abs() < 1e-3);
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use crate::tracer::{BoundingBox, Vec3};
+use std::f32::consts::PI;
+
+/// Where a light is, which way it faces and how bright it is, used to guess
+/// how much it lights a point without sampling it (Conty and Kulla 2018)
+#[derive(Copy, Clone, Debug)]
+pub struct LightBounds {
+    pub bbox: BoundingBox,
+    /// Total light given off
+    pub power: f32,
+    /// Center of the cone holding every normal of the light
+    pub axis: Vec3,
+    /// Cosine of the angle from axis to the edge of the normal cone
+    pub cos_theta_o: f32,
+    /// Cosine of the widest angle past its normal that light leaves at, 0
+    /// for diffuse emitters
+    pub cos_theta_e: f32,
+    /// Whether light also leaves against the normals
+    pub two_sided: bool,
+}
+
+impl LightBounds {
+    /// Bounds of a light shining in every direction
+    pub fn omnidirectional(bbox: BoundingBox, power: f32) -> LightBounds {
+        LightBounds {
+            bbox,
+            power,
+            axis: Vec3::new(0.0, 1.0, 0.0),
+            cos_theta_o: -1.0,
+            cos_theta_e: 0.0,
+            two_sided: false,
+        }
+    }
+
+    /// Bounds holding both a and b
+    pub fn union(a: &LightBounds, b: &LightBounds) -> LightBounds {
+        if a.power <= 0.0 {
+            return *b;
+        }
+        if b.power <= 0.0 {
+            return *a;
+        }
+
+        let (axis, cos_theta_o) = union_cones(a.axis, a.cos_theta_o, b.axis, b.cos_theta_o);
+        LightBounds {
+            bbox: BoundingBox::surrounding_box(&a.bbox, &b.bbox),
+            power: a.power + b.power,
+            axis,
+            cos_theta_o,
+            cos_theta_e: a.cos_theta_e.min(b.cos_theta_e),
+            two_sided: a.two_sided || b.two_sided,
+        }
+    }
+
+    pub fn centroid(&self) -> Vec3 {
+        0.5 * (self.bbox.min + self.bbox.max)
+    }
+
+    /// Estimate of the light reaching p, which lies on a surface with normal
+    /// n when given. Conservative, so it is only 0 when no light can reach.
+    pub fn importance(&self, p: Vec3, n: Option<Vec3>) -> f32 {
+        let center = self.centroid();
+        let radius = (self.bbox.max - center).length();
+        let to_point = p - center;
+        // Keep points close to or inside the bounds from blowing up
+        let distance_squared = to_point.squared_length().max(radius);
+        if to_point.squared_length() <= 0.0 {
+            return self.power / distance_squared.max(1e-6);
+        }
+        let wi = to_point.unit_vector();
+
+        let mut cos_theta_w = self.axis.dot(&wi);
+        if self.two_sided {
+            cos_theta_w = cos_theta_w.abs();
+        }
+        let sin_theta_w = (1.0 - cos_theta_w * cos_theta_w).max(0.0).sqrt();
+
+        // Half angle of the cone of directions from p that can reach the bounds
+        let cos_theta_b = if to_point.squared_length() <= radius * radius {
+            -1.0
+        } else {
+            (1.0 - radius * radius / to_point.squared_length())
+                .max(0.0)
+                .sqrt()
+        };
+        let sin_theta_b = (1.0 - cos_theta_b * cos_theta_b).max(0.0).sqrt();
+
+        // Smallest angle between a normal in the cone and a direction towards p
+        let sin_theta_o = (1.0 - self.cos_theta_o * self.cos_theta_o).max(0.0).sqrt();
+        let cos_theta_x = cos_sub_clamped(sin_theta_w, cos_theta_w, sin_theta_o, self.cos_theta_o);
+        let sin_theta_x = sin_sub_clamped(sin_theta_w, cos_theta_w, sin_theta_o, self.cos_theta_o);
+        let cos_theta_p = cos_sub_clamped(sin_theta_x, cos_theta_x, sin_theta_b, cos_theta_b);
+        if cos_theta_p <= self.cos_theta_e {
+            return 0.0;
+        }
+
+        let mut importance = self.power * cos_theta_p / distance_squared;
+        if let Some(n) = n {
+            let cos_theta_i = wi.dot(&n.unit_vector()).abs();
+            let sin_theta_i = (1.0 - cos_theta_i * cos_theta_i).max(0.0).sqrt();
+            importance *= cos_sub_clamped(sin_theta_i, cos_theta_i, sin_theta_b, cos_theta_b);
+        }
+        importance.max(0.0)
+    }
+
+    /// Whether the bounds hold p, give or take a little for rounding
+    pub fn contains(&self, p: Vec3) -> bool {
+        let slack = 1e-3 * (1.0 + (self.bbox.max - self.bbox.min).length());
+        (0..3).all(|i| {
+            p.index(i) >= self.bbox.min.index(i) - slack
+                && p.index(i) <= self.bbox.max.index(i) + slack
+        })
+    }
+}
+
+/// cos(max(0, a - b)) from the sines and cosines of a and b
+fn cos_sub_clamped(sin_a: f32, cos_a: f32, sin_b: f32, cos_b: f32) -> f32 {
+    if cos_a > cos_b {
+        return 1.0;
+    }
+    cos_a * cos_b + sin_a * sin_b
+}
+
+/// sin(max(0, a - b)) from the sines and cosines of a and b
+fn sin_sub_clamped(sin_a: f32, cos_a: f32, sin_b: f32, cos_b: f32) -> f32 {
+    if cos_a > cos_b {
+        return 0.0;
+    }
+    sin_a * cos_b - cos_a * sin_b
+}
+
+/// Smallest cone, given by axis and the cosine of its half angle, holding
+/// both cones
+fn union_cones(a: Vec3, cos_a: f32, b: Vec3, cos_b: f32) -> (Vec3, f32) {
+    if cos_a <= -1.0 || cos_b <= -1.0 {
+        return (a, -1.0);
+    }
+
+    let theta_a = cos_a.clamp(-1.0, 1.0).acos();
+    let theta_b = cos_b.clamp(-1.0, 1.0).acos();
+    let theta_d = a.dot(&b).clamp(-1.0, 1.0).acos();
+    if (theta_d + theta_b).min(PI) <= theta_a {
+        return (a, cos_a);
+    }
+    if (theta_d + theta_a).min(PI) <= theta_b {
+        return (b, cos_b);
+    }
+
+    let theta_o = 0.5 * (theta_a + theta_d + theta_b);
+    let rotation_axis = a.cross(&b);
+    if theta_o >= PI || rotation_axis.squared_length() <= 1e-12 {
+        return (a, -1.0);
+    }
+
+    // Turn a towards b until the cone's edge meets a's
+    let theta_r = theta_o - theta_a;
+    let k = rotation_axis.unit_vector();
+    let axis = theta_r.cos() * a + theta_r.sin() * k.cross(&a);
+    (axis.unit_vector(), theta_o.cos())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bounds_at(x: f32, axis: Vec3) -> LightBounds {
+        let p = Vec3::new(x, 0.0, 0.0);
+        LightBounds {
+            bbox: BoundingBox { min: p, max: p },
+            power: 1.0,
+            axis,
+            cos_theta_o: 1.0,
+            cos_theta_e: 0.0,
+            two_sided: false,
+        }
+    }
+
+    #[test]
+    fn importance_favors_near_lights_facing_the_point() {
+        let up = Vec3::new(0.0, 1.0, 0.0);
+        let p = Vec3::new(0.0, 1.0, 0.0);
+        let near = bounds_at(0.0, up).importance(p, None);
+        let far = bounds_at(4.0, up).importance(p, None);
+        assert!(near > far && far > 0.0);
+        assert_eq!(bounds_at(0.0, -up).importance(p, None), 0.0);
+    }
+
+    #[test]
+    fn union_cones_hold_both_cones() {
+        let x = Vec3::new(1.0, 0.0, 0.0);
+        let y = Vec3::new(0.0, 1.0, 0.0);
+        let (axis, cos_theta) = union_cones(x, 1.0, y, 1.0);
+        assert!((cos_theta - (PI / 4.0).cos()).abs() < 1e-5);
+        assert!((axis.dot(&x) - cos_theta).abs() < 1e-5);
+        assert!((axis.dot(&y) - cos_theta).abs() < 1e-5);
+        assert_eq!(union_cones(x, 1.0, -x, 1.0).1, -1.0);
+    }
+}
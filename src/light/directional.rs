@@ -0,0 +1,35 @@
+use super::lighting::{BoxLight, Light, LightSample};
+use crate::tracer::Vec3;
+
+/// Parallel light from infinitely far away, like sunlight
+#[derive(Copy, Clone, Debug)]
+pub struct DirectionalLight {
+    /// Where the light travels, i.e. down for a sun at noon
+    pub direction: Vec3,
+    /// Light arriving on a surface facing the light
+    pub irradiance: Vec3,
+}
+
+impl Light for DirectionalLight {
+    fn sample(&self, _p: Vec3, _u: (f32, f32)) -> Option<LightSample> {
+        Some(LightSample {
+            direction: -self.direction.unit_vector(),
+            distance: f32::MAX,
+            radiance: self.irradiance,
+            pdf: 1.0,
+            delta: true,
+        })
+    }
+
+    fn pdf(&self, _p: Vec3, _direction: Vec3) -> f32 {
+        0.0
+    }
+
+    fn is_infinite(&self) -> bool {
+        true
+    }
+
+    fn box_clone(&self) -> BoxLight {
+        Box::new(*self)
+    }
+}
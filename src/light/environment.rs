@@ -0,0 +1,33 @@
+use super::lighting::{BoxLight, Light, LightSample};
+use crate::tracer::{Environment, Vec3};
+
+/// A map or sky environment, lighting the scene from infinitely far away
+#[derive(Clone)]
+pub struct EnvironmentLight {
+    pub environment: Environment,
+}
+
+impl Light for EnvironmentLight {
+    fn sample(&self, _p: Vec3, u: (f32, f32)) -> Option<LightSample> {
+        let (direction, pdf) = self.environment.sample(u)?;
+        Some(LightSample {
+            direction,
+            distance: f32::MAX,
+            radiance: self.environment.radiance(direction),
+            pdf,
+            delta: false,
+        })
+    }
+
+    fn pdf(&self, _p: Vec3, direction: Vec3) -> f32 {
+        self.environment.pdf(direction)
+    }
+
+    fn is_infinite(&self) -> bool {
+        true
+    }
+
+    fn box_clone(&self) -> BoxLight {
+        Box::new(self.clone())
+    }
+}
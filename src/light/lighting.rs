@@ -0,0 +1,47 @@
+use super::bounds::LightBounds;
+use crate::tracer::Vec3;
+
+/// Light arriving at a point from one sampled direction
+#[derive(Copy, Clone, Debug)]
+pub struct LightSample {
+    /// Unit direction from the point towards the light
+    pub direction: Vec3,
+    /// Distance to the light along direction, shadow rays stop short of it
+    pub distance: f32,
+    /// Light arriving from the sampled direction. For delta lights this is
+    /// the light's irradiance at the point.
+    pub radiance: Vec3,
+    /// Solid angle density with which direction was picked, or just the
+    /// chance of picking the light for delta lights
+    pub pdf: f32,
+    /// Whether the light only arrives from this exact direction, which
+    /// scattered rays can never find
+    pub delta: bool,
+}
+
+pub trait Light: Sync + Send {
+    /// Pick a direction towards the light as seen from p using the 2D sample
+    /// u. None when no light reaches p.
+    fn sample(&self, p: Vec3, u: (f32, f32)) -> Option<LightSample>;
+    /// Solid angle density with which sample picks direction from p
+    fn pdf(&self, p: Vec3, direction: Vec3) -> f32;
+    /// Whether the light is infinitely far away, so only rays that miss
+    /// the world reach it
+    fn is_infinite(&self) -> bool {
+        false
+    }
+    /// Where and how bright the light is, so it can be picked by how much
+    /// it lights a point. Lights without bounds are picked uniformly.
+    fn bounds(&self) -> Option<LightBounds> {
+        None
+    }
+    fn box_clone(&self) -> BoxLight;
+}
+
+pub type BoxLight = Box<dyn Light>;
+
+impl Clone for Box<dyn Light> {
+    fn clone(&self) -> Box<dyn Light> {
+        self.box_clone()
+    }
+}
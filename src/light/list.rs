@@ -0,0 +1,326 @@
+use super::area::AreaLight;
+use super::bounds::LightBounds;
+use super::environment::EnvironmentLight;
+use super::lighting::{BoxLight, LightSample};
+use crate::tracer::{Hitable, Scene, Vec3};
+use std::cmp::Ordering;
+
+/// Largest f32 below 1, keeps rescaled samples in 0..1
+const ONE_MINUS_EPSILON: f32 = 1.0 - f32::EPSILON;
+
+/// Node of the tree over the bounded lights. Children of interior nodes are
+/// indices into the same list of nodes.
+#[derive(Clone, Debug)]
+enum LightNode {
+    Leaf {
+        bounds: LightBounds,
+        light: usize,
+    },
+    Interior {
+        bounds: LightBounds,
+        children: [usize; 2],
+    },
+}
+
+impl LightNode {
+    fn bounds(&self) -> &LightBounds {
+        match self {
+            LightNode::Leaf { bounds, .. } | LightNode::Interior { bounds, .. } => bounds,
+        }
+    }
+}
+
+/// The lights of a scene, one of which is picked at random for each shadow
+/// ray. Lights with bounds are kept in a tree and picked by how much they
+/// are expected to light the point (Conty and Kulla 2018), so only a few of
+/// thousands of small lights are ever considered. The rest are picked
+/// uniformly.
+#[derive(Clone, Default)]
+pub struct LightList {
+    lights: Vec<BoxLight>,
+    /// Lights without bounds, like environments and directional lights
+    unbounded: Vec<usize>,
+    /// Tree over the bounded lights, rooted at the first node
+    nodes: Vec<LightNode>,
+}
+
+impl LightList {
+    pub fn new(lights: Vec<BoxLight>) -> LightList {
+        let mut unbounded = Vec::new();
+        let mut bounded = Vec::new();
+        for (index, light) in lights.iter().enumerate() {
+            match light.bounds() {
+                Some(bounds) if bounds.power > 0.0 => bounded.push((index, bounds)),
+                // Lights that give off nothing are never worth a shadow ray
+                Some(_) => {}
+                None => unbounded.push(index),
+            }
+        }
+
+        let mut nodes = Vec::with_capacity(2 * bounded.len());
+        if !bounded.is_empty() {
+            build_tree(&mut nodes, &mut bounded);
+        }
+
+        LightList {
+            lights,
+            unbounded,
+            nodes,
+        }
+    }
+
+    /// Gather the scene's lights, every emissive hitable in its world, and
+    /// its environment when it is a map or sky
+    pub fn from_scene(scene: &Scene) -> LightList {
+        let mut lights: Vec<BoxLight> = scene
+            .world
+            .lights()
+            .into_iter()
+            .map(|hitable| Box::new(AreaLight { hitable }) as BoxLight)
+            .collect();
+        lights.extend(scene.lights.iter().cloned());
+        if scene.environment.is_light() {
+            lights.push(Box::new(EnvironmentLight {
+                environment: scene.environment.clone(),
+            }));
+        }
+        LightList::new(lights)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.unbounded.is_empty() && self.nodes.is_empty()
+    }
+
+    /// Chance of picking one of the unbounded lights rather than going
+    /// through the tree, which counts as a single light
+    fn unbounded_chance(&self) -> f32 {
+        let count = self.unbounded.len() as f32;
+        if self.nodes.is_empty() {
+            1.0
+        } else {
+            count / (count + 1.0)
+        }
+    }
+
+    /// Pick a light for p, which lies on a surface with normal n when given,
+    /// with the 1D sample pick and sample it with u. The pdf of the result
+    /// includes the chance of picking the light.
+    pub fn sample(
+        &self,
+        p: Vec3,
+        n: Option<Vec3>,
+        pick: f32,
+        u: (f32, f32),
+    ) -> Option<LightSample> {
+        let (index, chance) = self.pick(p, n, pick)?;
+        let mut sample = self.lights[index].sample(p, u)?;
+        sample.pdf *= chance;
+        Some(sample)
+    }
+
+    /// Index of the picked light and the chance it had of being picked
+    fn pick(&self, p: Vec3, n: Option<Vec3>, mut pick: f32) -> Option<(usize, f32)> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let unbounded_chance = self.unbounded_chance();
+        if pick < unbounded_chance {
+            let count = self.unbounded.len();
+            let index = ((pick / unbounded_chance * count as f32) as usize).min(count - 1);
+            return Some((self.unbounded[index], unbounded_chance / count as f32));
+        }
+
+        pick = ((pick - unbounded_chance) / (1.0 - unbounded_chance)).min(ONE_MINUS_EPSILON);
+        let mut chance = 1.0 - unbounded_chance;
+        let mut node = 0;
+        loop {
+            match &self.nodes[node] {
+                LightNode::Leaf { light, .. } => return Some((*light, chance)),
+                LightNode::Interior { children, .. } => {
+                    let left = self.nodes[children[0]].bounds().importance(p, n);
+                    let right = self.nodes[children[1]].bounds().importance(p, n);
+                    if left + right <= 0.0 {
+                        return None;
+                    }
+
+                    // Reuse pick for the next choice so it stays stratified
+                    let left_chance = left / (left + right);
+                    if pick < left_chance {
+                        pick = (pick / left_chance).min(ONE_MINUS_EPSILON);
+                        chance *= left_chance;
+                        node = children[0];
+                    } else {
+                        pick = ((pick - left_chance) / (1.0 - left_chance)).min(ONE_MINUS_EPSILON);
+                        chance *= 1.0 - left_chance;
+                        node = children[1];
+                    }
+                }
+            }
+        }
+    }
+
+    /// Solid angle density with which sample picks direction from p, normal
+    /// n. hit is where the ray along direction hit an emitter, None when it
+    /// escaped the world, and only lights that can be there count.
+    pub fn pdf(&self, p: Vec3, n: Option<Vec3>, direction: Vec3, hit: Option<Vec3>) -> f32 {
+        if self.is_empty() {
+            return 0.0;
+        }
+
+        let unbounded_chance = self.unbounded_chance();
+        let mut pdf = 0.0;
+        for index in &self.unbounded {
+            let light = &self.lights[*index];
+            if light.is_infinite() == hit.is_none() {
+                pdf += unbounded_chance / self.unbounded.len() as f32 * light.pdf(p, direction);
+            }
+        }
+
+        if let Some(hit) = hit {
+            if !self.nodes.is_empty() {
+                pdf += self.tree_pdf(0, 1.0 - unbounded_chance, p, n, direction, hit);
+            }
+        }
+        pdf
+    }
+
+    /// Density through the lights below node, which is reached with chance.
+    /// Only the branches whose bounds hold hit are followed.
+    fn tree_pdf(
+        &self,
+        node: usize,
+        chance: f32,
+        p: Vec3,
+        n: Option<Vec3>,
+        direction: Vec3,
+        hit: Vec3,
+    ) -> f32 {
+        match &self.nodes[node] {
+            LightNode::Leaf { light, .. } => chance * self.lights[*light].pdf(p, direction),
+            LightNode::Interior { children, .. } => {
+                let importances = [
+                    self.nodes[children[0]].bounds().importance(p, n),
+                    self.nodes[children[1]].bounds().importance(p, n),
+                ];
+                let total = importances[0] + importances[1];
+                if total <= 0.0 {
+                    return 0.0;
+                }
+
+                children
+                    .iter()
+                    .zip(&importances)
+                    .filter(|(child, importance)| {
+                        **importance > 0.0 && self.nodes[**child].bounds().contains(hit)
+                    })
+                    .map(|(child, importance)| {
+                        self.tree_pdf(*child, chance * importance / total, p, n, direction, hit)
+                    })
+                    .sum()
+            }
+        }
+    }
+}
+
+/// Add the subtree over lights to nodes, splitting them at the median along
+/// the axis their centers spread out the most. Returns the subtree's root.
+fn build_tree(nodes: &mut Vec<LightNode>, lights: &mut [(usize, LightBounds)]) -> usize {
+    let index = nodes.len();
+    if lights.len() == 1 {
+        nodes.push(LightNode::Leaf {
+            bounds: lights[0].1,
+            light: lights[0].0,
+        });
+        return index;
+    }
+
+    let bounds = lights
+        .iter()
+        .skip(1)
+        .fold(lights[0].1, |bounds, (_, light)| {
+            LightBounds::union(&bounds, light)
+        });
+    let spread = |axis: i32| {
+        let centers = lights.iter().map(|(_, light)| light.centroid().index(axis));
+        let (min, max) = centers.fold((f32::MAX, f32::MIN), |(min, max), c| {
+            (min.min(c), max.max(c))
+        });
+        max - min
+    };
+    let axis = (0..3)
+        .max_by(|a, b| {
+            spread(*a)
+                .partial_cmp(&spread(*b))
+                .unwrap_or(Ordering::Equal)
+        })
+        .unwrap_or(0);
+    lights.sort_by(|(_, a), (_, b)| {
+        a.centroid()
+            .index(axis)
+            .partial_cmp(&b.centroid().index(axis))
+            .unwrap_or(Ordering::Equal)
+    });
+
+    nodes.push(LightNode::Interior {
+        bounds,
+        children: [0, 0],
+    });
+    let (left, right) = lights.split_at_mut(lights.len() / 2);
+    let children = [build_tree(nodes, left), build_tree(nodes, right)];
+    if let LightNode::Interior {
+        children: node_children,
+        ..
+    } = &mut nodes[index]
+    {
+        *node_children = children;
+    }
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::Sphere;
+    use crate::material::diffuse_light;
+
+    fn sphere_lights() -> LightList {
+        let lights = (0..16)
+            .map(|i| {
+                Box::new(AreaLight {
+                    hitable: Box::new(Sphere {
+                        center: Vec3::new(i as f32 * 3.0, 2.0, 0.0),
+                        radius: 0.5,
+                        material: diffuse_light(1.0, 1.0, 1.0),
+                    }),
+                }) as BoxLight
+            })
+            .collect();
+        LightList::new(lights)
+    }
+
+    #[test]
+    fn near_lights_are_picked_more_often() {
+        let list = sphere_lights();
+        let p = Vec3::new(0.0, 0.0, 0.0);
+        let n = Some(Vec3::new(0.0, 1.0, 0.0));
+        let (near, near_chance) = list.pick(p, n, 0.0).unwrap();
+        let (far, far_chance) = list.pick(p, n, 0.999).unwrap();
+        assert_eq!((near, far), (0, 15));
+        assert!(near_chance > 10.0 * far_chance);
+    }
+
+    #[test]
+    fn pdf_matches_the_sampled_density() {
+        let list = sphere_lights();
+        let p = Vec3::new(10.0, 0.0, 0.0);
+        let n = Some(Vec3::new(0.0, 1.0, 0.0));
+        for i in 0..20 {
+            let pick = (i as f32 + 0.5) / 20.0;
+            let sample = list.sample(p, n, pick, (0.3, 0.6)).unwrap();
+            let hit = p + sample.distance * sample.direction;
+            let pdf = list.pdf(p, n, sample.direction, Some(hit));
+            assert!((pdf - sample.pdf).abs() < 1e-3 * sample.pdf);
+        }
+    }
+}
@@ -0,0 +1,17 @@
+mod area;
+mod bounds;
+mod directional;
+mod environment;
+mod lighting;
+mod list;
+mod point;
+mod spot;
+
+pub use area::*;
+pub use bounds::*;
+pub use directional::*;
+pub use environment::*;
+pub use lighting::*;
+pub use list::*;
+pub use point::*;
+pub use spot::*;
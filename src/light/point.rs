@@ -0,0 +1,66 @@
+use super::bounds::LightBounds;
+use super::lighting::{BoxLight, Light, LightSample};
+use crate::tracer::{luminance, BoundingBox, Vec3};
+use std::f32::consts::PI;
+
+/// Light shining equally in every direction from a single point
+#[derive(Copy, Clone, Debug)]
+pub struct PointLight {
+    pub position: Vec3,
+    /// Light given off per unit of solid angle
+    pub intensity: Vec3,
+}
+
+impl Light for PointLight {
+    fn sample(&self, p: Vec3, _u: (f32, f32)) -> Option<LightSample> {
+        let to_light = self.position - p;
+        let distance_squared = to_light.squared_length();
+        if distance_squared <= 0.0 {
+            return None;
+        }
+
+        Some(LightSample {
+            direction: to_light.unit_vector(),
+            distance: distance_squared.sqrt(),
+            radiance: self.intensity / distance_squared,
+            pdf: 1.0,
+            delta: true,
+        })
+    }
+
+    fn pdf(&self, _p: Vec3, _direction: Vec3) -> f32 {
+        0.0
+    }
+
+    fn bounds(&self) -> Option<LightBounds> {
+        let bbox = BoundingBox {
+            min: self.position,
+            max: self.position,
+        };
+        Some(LightBounds::omnidirectional(
+            bbox,
+            4.0 * PI * luminance(self.intensity),
+        ))
+    }
+
+    fn box_clone(&self) -> BoxLight {
+        Box::new(*self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn falls_off_with_the_square_of_the_distance() {
+        let light = PointLight {
+            position: Vec3::new(0.0, 2.0, 0.0),
+            intensity: Vec3::new(8.0, 8.0, 8.0),
+        };
+        let sample = light.sample(Vec3::new(0.0, 0.0, 0.0), (0.5, 0.5)).unwrap();
+        assert_eq!(sample.radiance.x, 2.0);
+        assert_eq!(sample.distance, 2.0);
+        assert_eq!(sample.direction.y, 1.0);
+    }
+}
@@ -0,0 +1,113 @@
+use super::bounds::LightBounds;
+use super::lighting::{BoxLight, Light, LightSample};
+use crate::tracer::{luminance, BoundingBox, Vec3};
+use std::f32::consts::PI;
+
+/// Point light shining in a cone, fading out towards the cone's edge
+#[derive(Copy, Clone, Debug)]
+pub struct SpotLight {
+    pub position: Vec3,
+    /// Where the cone points
+    pub direction: Vec3,
+    /// Light given off per unit of solid angle inside the cone
+    pub intensity: Vec3,
+    /// Angle in degrees from direction to the edge of the cone
+    pub cone_angle: f32,
+    /// Angle in degrees, inside the edge of the cone, over which the light
+    /// fades out. 0 gives a hard edge.
+    pub falloff: f32,
+}
+
+impl SpotLight {
+    /// Fraction of intensity shone towards the unit direction
+    fn attenuation(&self, direction: Vec3) -> f32 {
+        let cos_angle = direction.dot(&self.direction.unit_vector());
+        let cos_outer = self.cone_angle.to_radians().cos();
+        let cos_inner = (self.cone_angle - self.falloff.max(0.0))
+            .max(0.0)
+            .to_radians()
+            .cos();
+        if cos_angle <= cos_outer {
+            return 0.0;
+        }
+        if cos_angle >= cos_inner {
+            return 1.0;
+        }
+
+        let t = (cos_angle - cos_outer) / (cos_inner - cos_outer);
+        t * t * (3.0 - 2.0 * t)
+    }
+}
+
+impl Light for SpotLight {
+    fn sample(&self, p: Vec3, _u: (f32, f32)) -> Option<LightSample> {
+        let to_light = self.position - p;
+        let distance_squared = to_light.squared_length();
+        if distance_squared <= 0.0 {
+            return None;
+        }
+
+        let direction = to_light.unit_vector();
+        let attenuation = self.attenuation(-direction);
+        if attenuation <= 0.0 {
+            return None;
+        }
+
+        Some(LightSample {
+            direction,
+            distance: distance_squared.sqrt(),
+            radiance: self.intensity * (attenuation / distance_squared),
+            pdf: 1.0,
+            delta: true,
+        })
+    }
+
+    fn pdf(&self, _p: Vec3, _direction: Vec3) -> f32 {
+        0.0
+    }
+
+    /// The light leaves along a single normal, spread over the cone
+    fn bounds(&self) -> Option<LightBounds> {
+        let cos_cone = self.cone_angle.to_radians().cos();
+        Some(LightBounds {
+            bbox: BoundingBox {
+                min: self.position,
+                max: self.position,
+            },
+            power: 2.0 * PI * (1.0 - cos_cone) * luminance(self.intensity),
+            axis: self.direction.unit_vector(),
+            cos_theta_o: 1.0,
+            cos_theta_e: cos_cone,
+            two_sided: false,
+        })
+    }
+
+    fn box_clone(&self) -> BoxLight {
+        Box::new(*self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fades_out_towards_the_edge_of_the_cone() {
+        let light = SpotLight {
+            position: Vec3::new(0.0, 1.0, 0.0),
+            direction: Vec3::new(0.0, -1.0, 0.0),
+            intensity: Vec3::new(1.0, 1.0, 1.0),
+            cone_angle: 45.0,
+            falloff: 15.0,
+        };
+        let at = |x: f32| {
+            light
+                .sample(Vec3::new(x, 0.0, 0.0), (0.5, 0.5))
+                .map_or(0.0, |sample| sample.radiance.x * (1.0 + x * x))
+        };
+        assert_eq!(at(0.0), 1.0);
+        assert!((at(0.5) - 1.0).abs() < 1e-5);
+        assert!(at(0.8) > 0.0 && at(0.8) < 1.0);
+        assert_eq!(at(1.1), 0.0);
+    }
+}
@@ -1,25 +1,73 @@
+use super::sampler::{sample_1d, sample_2d, uniform_ball, uniform_sphere};
 use super::texture::{BoxTexture, ConstantTexture, ImageTexture, Texture};
 use super::tracer::*;
 use super::utils::read_image;
-use rand::prelude::*;
+use std::f32::consts::PI;
+
+/// Total light given off by a light, in physical units. Scene units are
+/// taken to be meters.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Power {
+    Watts(f32),
+    /// Converted to watts at 683 lumens per watt, the efficacy of green
+    /// light at 555 nm
+    Lumens(f32),
+}
+
+impl Power {
+    pub fn watts(self) -> f32 {
+        match self {
+            Power::Watts(watts) => watts,
+            Power::Lumens(lumens) => lumens / 683.0,
+        }
+    }
+}
+
+/// Kinds of bounces, each with its own depth limit
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum BounceKind {
+    Diffuse,
+    Specular,
+    Volume,
+}
 
 #[derive(Clone)]
 pub enum Material {
-    Lambertion { albedo: BoxTexture },
-    Metal { albedo: Vec3, fuzz: f32 },
-    Dielectric { ref_idx: f32 },
-    DiffuseLight { emit: BoxTexture },
-    Isotropic { albedo: BoxTexture },
+    Lambertion {
+        albedo: BoxTexture,
+    },
+    Metal {
+        albedo: Vec3,
+        fuzz: f32,
+    },
+    Dielectric {
+        ref_idx: f32,
+    },
+    /// Gives off intensity times emit, from the side its normal points to
+    /// unless two_sided is set
+    DiffuseLight {
+        emit: BoxTexture,
+        intensity: f32,
+        two_sided: bool,
+    },
+    Isotropic {
+        albedo: BoxTexture,
+    },
 }
 
 impl Material {
     fn lambertion_scatter(_r: &Ray, rec: &HitRecord, albedo: &BoxTexture) -> Option<(Vec3, Ray)> {
-        let target = rec.p + rec.normal + Vec3::random_in_unit_sphere();
+        // A point on the unit sphere around the normal's tip gives cosine
+        // weighted directions, the exact Lambertian distribution
+        let mut direction = rec.normal + uniform_sphere(sample_2d());
+        if direction.squared_length() < 1e-8 {
+            direction = rec.normal;
+        }
         return Some((
-            albedo.value(rec.u, rec.v, rec.p),
+            albedo.value_at(rec),
             Ray {
                 origin: rec.p,
-                direction: target - rec.p,
+                direction,
             },
         ));
     }
@@ -43,7 +91,7 @@ impl Material {
         let reflected = Material::reflect(&r.direction.unit_vector(), &rec.normal);
         let scattered = Ray {
             origin: rec.p,
-            direction: reflected + fuzz * Vec3::random_in_unit_sphere(),
+            direction: reflected + fuzz * uniform_ball(sample_2d(), sample_1d()),
         };
 
         if scattered.direction.dot(&rec.normal) > 0.0 {
@@ -86,7 +134,7 @@ impl Material {
         };
 
         let reflect_prob = Material::schlick(cosine, ref_idx);
-        if random::<f32>() >= reflect_prob {
+        if sample_1d() >= reflect_prob {
             return Some((
                 attenuation,
                 Ray {
@@ -102,10 +150,10 @@ impl Material {
     fn isotropic_scatter(_r: &Ray, rec: &HitRecord, albedo: &BoxTexture) -> Option<(Vec3, Ray)> {
         let scattered = Ray {
             origin: rec.p,
-            direction: Vec3::random_in_unit_sphere(),
+            direction: uniform_sphere(sample_2d()),
         };
 
-        let attenuation = albedo.value(rec.u, rec.v, rec.p);
+        let attenuation = albedo.value_at(rec);
         Some((attenuation, scattered))
     }
 
@@ -119,12 +167,167 @@ impl Material {
         }
     }
 
-    pub fn emitted(&self, u: f32, v: f32, p: Vec3) -> Vec3 {
+    pub fn bounce_kind(&self) -> BounceKind {
         match self {
-            Material::DiffuseLight { emit } => emit.value(u, v, p),
+            Material::Lambertion { .. } | Material::DiffuseLight { .. } => BounceKind::Diffuse,
+            Material::Metal { .. } | Material::Dielectric { .. } => BounceKind::Specular,
+            Material::Isotropic { .. } => BounceKind::Volume,
+        }
+    }
+
+    /// Base color of the surface at the hit, used to guide the denoiser
+    pub fn albedo(&self, rec: &HitRecord) -> Vec3 {
+        match self {
+            Material::Lambertion { albedo } => albedo.value_at(rec),
+            Material::Metal { albedo, .. } => *albedo,
+            Material::Dielectric { .. } => Vec3::new(1.0, 1.0, 1.0),
+            Material::DiffuseLight { .. } => Vec3::new(1.0, 1.0, 1.0),
+            Material::Isotropic { albedo } => albedo.value_at(rec),
+        }
+    }
+
+    /// Light scattered towards -r.direction per unit of light arriving from
+    /// direction, cosine included. Zero for specular materials, which only
+    /// scatter into single directions.
+    pub fn eval(&self, r: &Ray, rec: &HitRecord, direction: Vec3) -> Vec3 {
+        match self {
+            Material::Lambertion { albedo } => {
+                let cosine = rec.normal.unit_vector().dot(&direction.unit_vector());
+                albedo.value_at(rec) * (cosine.max(0.0) / PI)
+            }
+            // scatter's attenuation is eval / pdf, and always albedo
+            Material::Metal { albedo, fuzz } if *fuzz > 0.0 => {
+                if direction.dot(&rec.normal) <= 0.0 {
+                    return Vec3::new(0.0, 0.0, 0.0);
+                }
+                *albedo * Material::metal_pdf(r, rec, *fuzz, direction)
+            }
+            Material::Isotropic { albedo } => albedo.value_at(rec) / (4.0 * PI),
             _ => Vec3::new(0.0, 0.0, 0.0),
         }
     }
+
+    /// Solid angle density with which scatter picks direction, 0 for specular
+    /// materials
+    pub fn pdf(&self, r: &Ray, rec: &HitRecord, direction: Vec3) -> f32 {
+        match self {
+            Material::Lambertion { .. } => {
+                let cosine = rec.normal.unit_vector().dot(&direction.unit_vector());
+                cosine.max(0.0) / PI
+            }
+            Material::Metal { fuzz, .. } if *fuzz > 0.0 => {
+                Material::metal_pdf(r, rec, *fuzz, direction)
+            }
+            Material::Isotropic { .. } => 1.0 / (4.0 * PI),
+            _ => 0.0,
+        }
+    }
+
+    /// Density of the directions through a ball of radius fuzz around the tip
+    /// of the reflected ray, which is where metal_scatter points
+    fn metal_pdf(r: &Ray, rec: &HitRecord, fuzz: f32, direction: Vec3) -> f32 {
+        let reflected = Material::reflect(&r.direction.unit_vector(), &rec.normal).unit_vector();
+        let cosine = reflected.dot(&direction.unit_vector());
+        // Distances along direction at which it enters and leaves the ball
+        let discriminant = cosine * cosine - 1.0 + fuzz * fuzz;
+        if discriminant <= 0.0 {
+            return 0.0;
+        }
+        let t_far = cosine + discriminant.sqrt();
+        if t_far <= 0.0 {
+            return 0.0;
+        }
+        let t_near = (cosine - discriminant.sqrt()).max(0.0);
+
+        // Volume of the cone through the ball over the volume of the ball
+        (t_far.powi(3) - t_near.powi(3)) / (4.0 * PI * fuzz.powi(3))
+    }
+
+    /// Whether the material only scatters into single directions, so lights
+    /// can't be sampled for it
+    pub fn is_specular(&self) -> bool {
+        match self {
+            Material::Metal { fuzz, .. } => *fuzz <= 0.0,
+            Material::Dielectric { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// Whether the material gives off light
+    pub fn is_emissive(&self) -> bool {
+        matches!(self, Material::DiffuseLight { .. })
+    }
+
+    /// Light given off at rec back along r, zero when r hits the unlit side.
+    /// The side comes from rec's normal, which for lights is the geometric
+    /// normal.
+    pub fn emitted(&self, r: &Ray, rec: &HitRecord) -> Vec3 {
+        if !self.is_two_sided() && r.direction.dot(&rec.normal) >= 0.0 {
+            return Vec3::new(0.0, 0.0, 0.0);
+        }
+        match self {
+            Material::DiffuseLight {
+                emit, intensity, ..
+            } => *intensity * emit.value_at(rec),
+            _ => Vec3::new(0.0, 0.0, 0.0),
+        }
+    }
+
+    /// Light given off at a point on the lit sides
+    pub fn emission(&self, u: f32, v: f32, p: Vec3) -> Vec3 {
+        match self {
+            Material::DiffuseLight {
+                emit, intensity, ..
+            } => *intensity * emit.value(u, v, p),
+            _ => Vec3::new(0.0, 0.0, 0.0),
+        }
+    }
+
+    /// Whether the material gives off light from both sides
+    pub fn is_two_sided(&self) -> bool {
+        matches!(
+            self,
+            Material::DiffuseLight {
+                two_sided: true,
+                ..
+            }
+        )
+    }
+
+    /// Make a light give off light from both sides
+    pub fn two_sided(self) -> Material {
+        match self {
+            Material::DiffuseLight {
+                emit, intensity, ..
+            } => Material::DiffuseLight {
+                emit,
+                intensity,
+                two_sided: true,
+            },
+            material => material,
+        }
+    }
+
+    /// Set a light's intensity so it gives off power in total over a surface
+    /// of area, counting both sides when it is two sided. Its color is taken
+    /// as is, so colors with a luminance of 1 (like blackbody) give exactly
+    /// power. Take area from the light's `area()`, which is only approximate
+    /// for curved surfaces under a non-uniform scale.
+    pub fn with_power(self, power: Power, area: f32) -> Material {
+        match self {
+            Material::DiffuseLight {
+                emit, two_sided, ..
+            } => {
+                let sides = if two_sided { 2.0 } else { 1.0 };
+                Material::DiffuseLight {
+                    emit,
+                    intensity: power.watts() / (sides * PI * area).max(1e-8),
+                    two_sided,
+                }
+            }
+            material => material,
+        }
+    }
 }
 
 /// Create a basic lambertion material
@@ -158,16 +361,113 @@ pub fn dielectric(ref_idx: f32) -> Material {
     Material::Dielectric { ref_idx }
 }
 
-/// Create a basic diffuse light material
+/// Create a basic one sided diffuse light material
 pub fn diffuse_light(r: f32, g: f32, b: f32) -> Material {
+    textured_light(Box::new(ConstantTexture::new(r, g, b)), 1.0)
+}
+
+/// Create a one sided light whose color comes from a texture, scaled by
+/// intensity
+pub fn textured_light(emit: BoxTexture, intensity: f32) -> Material {
     Material::DiffuseLight {
-        emit: Box::new(ConstantTexture::new(r, g, b)),
+        emit,
+        intensity,
+        two_sided: false,
     }
 }
 
+/// Create a one sided light with the color of a black body at kelvin
+/// degrees, with luminance intensity
+pub fn blackbody_light(kelvin: f32, intensity: f32) -> Material {
+    let color = blackbody(kelvin);
+    textured_light(
+        Box::new(ConstantTexture::new(color.x, color.y, color.z)),
+        intensity,
+    )
+}
+
 /// Create a basic isotropic material
 pub fn isotropic(r: f32, g: f32, b: f32) -> Material {
     Material::Isotropic {
         albedo: Box::new(ConstantTexture::new(r, g, b)),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampler::uniform_sphere;
+
+    #[test]
+    fn pdfs_integrate_to_one_over_all_directions() {
+        let materials = [
+            lambertion(0.5, 0.5, 0.5),
+            metal(Vec3::new(0.5, 0.5, 0.5), 0.3),
+            metal(Vec3::new(0.5, 0.5, 0.5), 1.5),
+            isotropic(0.5, 0.5, 0.5),
+        ];
+        let r = Ray {
+            origin: Vec3::new(-1.0, 1.0, 0.0),
+            direction: Vec3::new(1.0, -1.0, 0.0),
+        };
+
+        let n = 400;
+        for material in &materials {
+            let rec = HitRecord {
+                t: 1.0,
+                u: 0.0,
+                v: 0.0,
+                p: Vec3::new(0.0, 0.0, 0.0),
+                normal: Vec3::new(0.0, 1.0, 0.0),
+                material,
+                object_id: 0,
+                vertex_color: None,
+            };
+            let mut integral = 0.0;
+            for i in 0..n {
+                for j in 0..n {
+                    let u = ((i as f32 + 0.5) / n as f32, (j as f32 + 0.5) / n as f32);
+                    integral += material.pdf(&r, &rec, uniform_sphere(u));
+                }
+            }
+            integral *= 4.0 * PI / (n * n) as f32;
+            assert!((integral - 1.0).abs() < 0.02, "{}", integral);
+        }
+    }
+
+    #[test]
+    fn lights_emit_from_the_lit_side_with_their_power() {
+        let light = blackbody_light(6500.0, 1.0).with_power(Power::Watts(2.0 * PI), 2.0);
+        let rec = HitRecord {
+            t: 1.0,
+            u: 0.0,
+            v: 0.0,
+            p: Vec3::new(0.0, 0.0, 0.0),
+            normal: Vec3::new(0.0, 1.0, 0.0),
+            material: &light,
+            object_id: 0,
+            vertex_color: None,
+        };
+        let front = Ray {
+            origin: Vec3::new(0.0, 1.0, 0.0),
+            direction: Vec3::new(0.0, -1.0, 0.0),
+        };
+        let back = Ray {
+            origin: Vec3::new(0.0, -1.0, 0.0),
+            direction: Vec3::new(0.0, 1.0, 0.0),
+        };
+        assert!((luminance(light.emitted(&front, &rec)) - 1.0).abs() < 1e-4);
+        assert_eq!(light.emitted(&back, &rec).y, 0.0);
+
+        let two_sided = light
+            .clone()
+            .two_sided()
+            .with_power(Power::Watts(2.0 * PI), 2.0);
+        let rec = HitRecord {
+            material: &two_sided,
+            ..rec
+        };
+        assert!((luminance(two_sided.emitted(&back, &rec)) - 0.5).abs() < 1e-4);
+        assert_eq!(Power::Lumens(683.0).watts(), 1.0);
+    }
+}
@@ -0,0 +1,513 @@
+use crate::tracer::Vec3;
+use inflate::inflate_bytes_zlib;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+
+/// A named channel of float samples, one per pixel in rows top to bottom
+#[derive(Clone, Debug)]
+pub struct ExrChannel {
+    pub name: String,
+    pub samples: Vec<f32>,
+}
+
+/// Split colors into R, G and B channels. A non empty layer is used as a
+/// prefix, giving channels like "albedo.R".
+pub fn rgb_channels(layer: &str, pixels: &[Vec3]) -> Vec<ExrChannel> {
+    let name = |channel: &str| {
+        if layer.is_empty() {
+            channel.to_string()
+        } else {
+            format!("{}.{}", layer, channel)
+        }
+    };
+
+    vec![
+        ExrChannel {
+            name: name("R"),
+            samples: pixels.iter().map(|col| col.x).collect(),
+        },
+        ExrChannel {
+            name: name("G"),
+            samples: pixels.iter().map(|col| col.y).collect(),
+        },
+        ExrChannel {
+            name: name("B"),
+            samples: pixels.iter().map(|col| col.z).collect(),
+        },
+    ]
+}
+
+/// Write rows of pixels (top to bottom) as an uncompressed RGB OpenEXR image
+pub fn write_exr(path: &str, nx: u32, ny: u32, pixels: &[Vec3]) -> io::Result<()> {
+    write_exr_channels(path, nx, ny, rgb_channels("", pixels))
+}
+
+/// Write any number of float channels to a single uncompressed OpenEXR image
+pub fn write_exr_channels(
+    path: &str,
+    nx: u32,
+    ny: u32,
+    channels: Vec<ExrChannel>,
+) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    encode_exr(&mut file, nx, ny, channels)?;
+    file.flush()
+}
+
+const UINT_PIXEL_TYPE: i32 = 0;
+const HALF_PIXEL_TYPE: i32 = 1;
+const FLOAT_PIXEL_TYPE: i32 = 2;
+
+/// Most a deflate stream expands by, RLE expands by less
+const MAX_COMPRESSION_RATIO: usize = 1032;
+
+pub fn encode_exr(
+    w: &mut impl Write,
+    nx: u32,
+    ny: u32,
+    mut channels: Vec<ExrChannel>,
+) -> io::Result<()> {
+    // Readers expect the channel list, and so the data in each line, sorted by name
+    channels.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut header: Vec<u8> = Vec::new();
+    header.extend(&20_000_630i32.to_le_bytes());
+    // Version 2, single part scanline image
+    header.extend(&2i32.to_le_bytes());
+
+    let mut chlist: Vec<u8> = Vec::new();
+    for channel in &channels {
+        chlist.extend(channel.name.as_bytes());
+        chlist.push(0);
+        chlist.extend(&FLOAT_PIXEL_TYPE.to_le_bytes());
+        // pLinear and three reserved bytes
+        chlist.extend(&[0, 0, 0, 0]);
+        // x and y sampling
+        chlist.extend(&1i32.to_le_bytes());
+        chlist.extend(&1i32.to_le_bytes());
+    }
+    chlist.push(0);
+    write_attribute(&mut header, "channels", "chlist", &chlist);
+
+    // No compression
+    write_attribute(&mut header, "compression", "compression", &[0]);
+
+    let mut window: Vec<u8> = Vec::new();
+    for val in &[0, 0, nx as i32 - 1, ny as i32 - 1] {
+        window.extend(&val.to_le_bytes());
+    }
+    write_attribute(&mut header, "dataWindow", "box2i", &window);
+    write_attribute(&mut header, "displayWindow", "box2i", &window);
+
+    // Increasing y
+    write_attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+    write_attribute(
+        &mut header,
+        "pixelAspectRatio",
+        "float",
+        &1f32.to_le_bytes(),
+    );
+    write_attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+    write_attribute(
+        &mut header,
+        "screenWindowWidth",
+        "float",
+        &1f32.to_le_bytes(),
+    );
+    header.push(0);
+    w.write_all(&header)?;
+
+    // Every scanline is its own chunk, found through a table of offsets that
+    // directly follows the header.
+    let line_size = nx as usize * channels.len() * 4;
+    let chunk_size = 8 + line_size;
+    let table_end = header.len() + ny as usize * 8;
+    for y in 0..ny as usize {
+        let offset = (table_end + y * chunk_size) as u64;
+        w.write_all(&offset.to_le_bytes())?;
+    }
+
+    for y in 0..ny as usize {
+        w.write_all(&(y as i32).to_le_bytes())?;
+        w.write_all(&(line_size as i32).to_le_bytes())?;
+        for channel in &channels {
+            let row = &channel.samples[y * nx as usize..(y + 1) * nx as usize];
+            for sample in row {
+                w.write_all(&sample.to_le_bytes())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn write_attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    header.extend(name.as_bytes());
+    header.push(0);
+    header.extend(kind.as_bytes());
+    header.push(0);
+    header.extend(&(value.len() as i32).to_le_bytes());
+    header.extend(value);
+}
+
+/// Read the R, G and B channels (or Y for grey images) of a scanline OpenEXR
+/// image as rows of pixels top to bottom, returned with the image's width and
+/// height
+pub fn read_exr(path: &str) -> io::Result<(Vec<Vec3>, u32, u32)> {
+    decode_exr(&fs::read(path)?)
+}
+
+/// Decode an uncompressed, RLE or ZIP compressed scanline OpenEXR image
+pub fn decode_exr(data: &[u8]) -> io::Result<(Vec<Vec3>, u32, u32)> {
+    let mut reader = ExrReader { data, pos: 0 };
+    if reader.i32()? != 20_000_630 {
+        return Err(invalid_exr("not an OpenEXR image"));
+    }
+    // Tiled, deep and multi part flags
+    if reader.i32()? & 0x1a00 != 0 {
+        return Err(invalid_exr(
+            "only single part scanline images are supported",
+        ));
+    }
+
+    let mut channels: Vec<(String, i32)> = Vec::new();
+    let mut compression = 0;
+    let mut window = None;
+    loop {
+        let name = reader.string()?;
+        if name.is_empty() {
+            break;
+        }
+        let _kind = reader.string()?;
+        let size = reader.size()?;
+        let mut value = ExrReader {
+            data: reader.bytes(size)?,
+            pos: 0,
+        };
+        match name.as_str() {
+            "channels" => loop {
+                let channel = value.string()?;
+                if channel.is_empty() {
+                    break;
+                }
+                let pixel_type = value.i32()?;
+                // pLinear and three reserved bytes
+                value.bytes(4)?;
+                if value.i32()? != 1 || value.i32()? != 1 {
+                    return Err(invalid_exr("subsampled channels are not supported"));
+                }
+                channels.push((channel, pixel_type));
+            },
+            "compression" => compression = value.bytes(1)?[0],
+            "dataWindow" => {
+                window = Some((value.i32()?, value.i32()?, value.i32()?, value.i32()?));
+            }
+            _ => {}
+        }
+    }
+
+    let (x0, y0, x1, y1) = window.ok_or_else(|| invalid_exr("missing dataWindow"))?;
+    let nx = i64::from(x1) - i64::from(x0) + 1;
+    let ny = i64::from(y1) - i64::from(y0) + 1;
+    if nx < 1 || ny < 1 {
+        return Err(invalid_exr("empty dataWindow"));
+    }
+    let (nx, ny) = (nx as usize, ny as usize);
+    let lines_per_chunk = match compression {
+        0..=2 => 1,
+        3 => 16,
+        _ => return Err(invalid_exr("only RLE and ZIP compression are supported")),
+    };
+
+    // Which of R, G and B each channel fills in
+    let has = |name: &str| channels.iter().any(|(channel, _)| channel == name);
+    let targets: Vec<Vec<usize>> = channels
+        .iter()
+        .map(|(name, _)| match name.as_str() {
+            "R" => vec![0],
+            "G" => vec![1],
+            "B" => vec![2],
+            "Y" if !has("R") && !has("G") && !has("B") => vec![0, 1, 2],
+            _ => Vec::new(),
+        })
+        .collect();
+    if targets.iter().all(Vec::is_empty) {
+        return Err(invalid_exr("no R, G, B or Y channels"));
+    }
+
+    let sample_size = |pixel_type: i32| if pixel_type == HALF_PIXEL_TYPE { 2 } else { 4 };
+    let line_size: usize = channels
+        .iter()
+        .map(|(_, pixel_type)| nx * sample_size(*pixel_type))
+        .sum();
+
+    // Check the window against the file before allocating for it. Compressed
+    // chunks can't hold more than MAX_COMPRESSION_RATIO times their size.
+    let max_ratio = if compression == 0 {
+        1
+    } else {
+        MAX_COMPRESSION_RATIO
+    };
+    let fits = line_size
+        .checked_mul(ny)
+        .is_some_and(|size| size / max_ratio <= data.len());
+    if !fits {
+        return Err(invalid_exr("dataWindow is larger than the image data"));
+    }
+
+    let mut pixels = vec![Vec3::new(0.0, 0.0, 0.0); nx * ny];
+    let chunks = ny.div_ceil(lines_per_chunk);
+    let mut offsets = Vec::new();
+    for _ in 0..chunks {
+        offsets.push(reader.u64()? as usize);
+    }
+
+    for offset in offsets {
+        reader.pos = offset;
+        let first_line = i64::from(reader.i32()?) - i64::from(y0);
+        if first_line < 0 || first_line >= ny as i64 {
+            return Err(invalid_exr("chunk is outside the dataWindow"));
+        }
+        let first_line = first_line as usize;
+        let size = reader.size()?;
+        let chunk = reader.bytes(size)?;
+        let lines = lines_per_chunk.min(ny.saturating_sub(first_line));
+        let expected = lines * line_size;
+
+        // Chunks that don't get smaller when compressed are stored as is
+        let chunk = if size == expected || compression == 0 {
+            chunk.to_vec()
+        } else if compression == 1 {
+            reorder_exr_bytes(decode_exr_rle(chunk)?)
+        } else {
+            reorder_exr_bytes(inflate_bytes_zlib(chunk).map_err(|e| invalid_exr(&e))?)
+        };
+        if chunk.len() != expected {
+            return Err(invalid_exr("chunk has the wrong size"));
+        }
+
+        let mut values = ExrReader {
+            data: &chunk,
+            pos: 0,
+        };
+        for line in first_line..first_line + lines {
+            for ((_, pixel_type), targets) in channels.iter().zip(&targets) {
+                for x in 0..nx {
+                    let value = match *pixel_type {
+                        UINT_PIXEL_TYPE => values.u32()? as f32,
+                        HALF_PIXEL_TYPE => half_to_f32(values.u16()?),
+                        FLOAT_PIXEL_TYPE => f32::from_bits(values.u32()?),
+                        _ => return Err(invalid_exr("unknown pixel type")),
+                    };
+                    for target in targets {
+                        pixels[line * nx + x].set_index(*target as i32, value);
+                    }
+                }
+            }
+        }
+    }
+
+    Ok((pixels, nx as u32, ny as u32))
+}
+
+/// Little endian values read from the front of a byte slice
+struct ExrReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ExrReader<'a> {
+    fn bytes(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        let data = self
+            .pos
+            .checked_add(len)
+            .and_then(|end| self.data.get(self.pos..end))
+            .ok_or_else(|| invalid_exr("unexpected end of data"))?;
+        self.pos += len;
+        Ok(data)
+    }
+
+    fn u16(&mut self) -> io::Result<u16> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn i32(&mut self) -> io::Result<i32> {
+        Ok(self.u32()? as i32)
+    }
+
+    /// Byte count of an attribute or chunk, which can't be negative
+    fn size(&mut self) -> io::Result<usize> {
+        let size = self.i32()?;
+        if size < 0 {
+            return Err(invalid_exr("negative size"));
+        }
+        Ok(size as usize)
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        Ok(self.u32()? as u64 | (self.u32()? as u64) << 32)
+    }
+
+    /// Null terminated string
+    fn string(&mut self) -> io::Result<String> {
+        let len = self.data[self.pos.min(self.data.len())..]
+            .iter()
+            .position(|byte| *byte == 0)
+            .ok_or_else(|| invalid_exr("unterminated string"))?;
+        let string = String::from_utf8_lossy(self.bytes(len)?).into_owned();
+        self.pos += 1;
+        Ok(string)
+    }
+}
+
+fn invalid_exr(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// Expand runs: a negative count is followed by that many literal bytes, any
+/// other count by a byte repeated count + 1 times
+fn decode_exr_rle(data: &[u8]) -> io::Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(data.len() * 2);
+    let mut i = 0;
+    while i < data.len() {
+        let count = data[i] as i8;
+        i += 1;
+        if count < 0 {
+            let len = -(count as isize) as usize;
+            let literal = data
+                .get(i..i + len)
+                .ok_or_else(|| invalid_exr("truncated run"))?;
+            out.extend(literal);
+            i += len;
+        } else {
+            let byte = *data.get(i).ok_or_else(|| invalid_exr("truncated run"))?;
+            out.extend(std::iter::repeat_n(byte, count as usize + 1));
+            i += 1;
+        }
+    }
+    Ok(out)
+}
+
+/// Undo the delta coding and byte splitting RLE and ZIP compression apply
+/// before compressing
+fn reorder_exr_bytes(mut data: Vec<u8>) -> Vec<u8> {
+    for i in 1..data.len() {
+        data[i] = data[i - 1].wrapping_add(data[i]).wrapping_sub(128);
+    }
+
+    // The first half holds the even bytes, the second half the odd ones
+    let half = data.len().div_ceil(2);
+    let mut out = Vec::with_capacity(data.len());
+    for i in 0..half {
+        out.push(data[i]);
+        if let Some(byte) = data.get(half + i) {
+            out.push(*byte);
+        }
+    }
+    out
+}
+
+/// Widen an IEEE half precision float
+fn half_to_f32(half: u16) -> f32 {
+    let sign = if half & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((half >> 10) & 0x1f) as i32;
+    let mantissa = (half & 0x3ff) as f32;
+    match exponent {
+        0 => sign * mantissa * 2f32.powi(-24),
+        0x1f if mantissa == 0.0 => sign * f32::INFINITY,
+        0x1f => f32::NAN,
+        _ => sign * (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_exr_reads_back_encoded_images() {
+        let pixels: Vec<Vec3> = (0..6)
+            .map(|i| Vec3::new(i as f32, 0.5 * i as f32, -(i as f32)))
+            .collect();
+        let mut data = Vec::new();
+        encode_exr(&mut data, 3, 2, rgb_channels("", &pixels)).unwrap();
+
+        let (decoded, nx, ny) = decode_exr(&data).unwrap();
+        assert_eq!((nx, ny), (3, 2));
+        for (a, b) in pixels.iter().zip(&decoded) {
+            assert_eq!((a.x, a.y, a.z), (b.x, b.y, b.z));
+        }
+    }
+
+    #[test]
+    fn decode_exr_rejects_malformed_headers() {
+        let pixels = vec![Vec3::new(1.0, 2.0, 3.0); 6];
+        let mut data = Vec::new();
+        encode_exr(&mut data, 3, 2, rgb_channels("", &pixels)).unwrap();
+        let size = data.windows(6).position(|w| w == b"box2i\0").unwrap() + 6;
+
+        let with_window = |window: [i32; 4]| {
+            let mut data = data.clone();
+            for (i, value) in window.iter().enumerate() {
+                let at = size + 4 + 4 * i;
+                data[at..at + 4].copy_from_slice(&value.to_le_bytes());
+            }
+            decode_exr(&data)
+        };
+        assert!(with_window([i32::MIN, 0, i32::MAX, 0]).is_err());
+        assert!(with_window([0, 0, 99_999, 99_999]).is_err());
+        assert!(with_window([2, 0, 0, 1]).is_err());
+
+        let mut negative = data.clone();
+        negative[size..size + 4].copy_from_slice(&(-1i32).to_le_bytes());
+        assert!(decode_exr(&negative).is_err());
+    }
+
+    #[test]
+    fn compressed_chunks_are_unpacked() {
+        assert_eq!(half_to_f32(0x3c00), 1.0);
+        assert_eq!(half_to_f32(0xc000), -2.0);
+        assert_eq!(half_to_f32(0x0001), 2f32.powi(-24));
+
+        assert_eq!(
+            decode_exr_rle(&[2, 7, (-2i8) as u8, 1, 2]).unwrap(),
+            vec![7, 7, 7, 1, 2]
+        );
+        // Bytes 1, 2, 3, 4, 5 split into 1, 3, 5 and 2, 4, then delta coded
+        assert_eq!(
+            reorder_exr_bytes(vec![1, 130, 130, 125, 130]),
+            vec![1, 2, 3, 4, 5]
+        );
+    }
+
+    #[test]
+    fn encode_exr_sorts_channels_and_offsets_lines() {
+        let pixels = vec![Vec3::new(1.0, 2.0, 3.0); 6];
+        let mut data = Vec::new();
+        encode_exr(&mut data, 3, 2, rgb_channels("", &pixels)).unwrap();
+
+        let chlist = data.windows(7).position(|w| w == b"chlist\0").unwrap();
+        assert_eq!(&data[chlist + 11..chlist + 13], b"B\0");
+
+        let header_end = data.len() - 2 * 8 - 2 * (8 + 3 * 3 * 4);
+        let first_offset = u64::from_le_bytes([
+            data[header_end],
+            data[header_end + 1],
+            data[header_end + 2],
+            data[header_end + 3],
+            data[header_end + 4],
+            data[header_end + 5],
+            data[header_end + 6],
+            data[header_end + 7],
+        ]);
+        assert_eq!(first_offset as usize, header_end + 16);
+        // The first value of the first line is from the B channel
+        let first_value = first_offset as usize + 8;
+        assert_eq!(&data[first_value..first_value + 4], &3f32.to_le_bytes());
+    }
+}
@@ -0,0 +1,7 @@
+pub mod exr;
+pub mod pfm;
+pub mod rgbe;
+
+pub use exr::*;
+pub use pfm::*;
+pub use rgbe::*;
@@ -0,0 +1,42 @@
+use crate::tracer::Vec3;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+/// Write rows of pixels (top to bottom) as a little endian color PFM image
+pub fn write_pfm(path: &str, nx: u32, ny: u32, pixels: &[Vec3]) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    encode_pfm(&mut file, nx, ny, pixels)?;
+    file.flush()
+}
+
+pub fn encode_pfm(w: &mut impl Write, nx: u32, ny: u32, pixels: &[Vec3]) -> io::Result<()> {
+    // A negative scale marks the data as little endian
+    write!(w, "PF\n{} {}\n-1.0\n", nx, ny)?;
+
+    // PFM stores rows from the bottom of the image up
+    for row in pixels.chunks(nx as usize).rev() {
+        for col in row {
+            w.write_all(&col.x.to_le_bytes())?;
+            w.write_all(&col.y.to_le_bytes())?;
+            w.write_all(&col.z.to_le_bytes())?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_pfm_writes_rows_bottom_up() {
+        let pixels = vec![Vec3::new(1.0, 2.0, 3.0), Vec3::new(4.0, 5.0, 6.0)];
+        let mut data = Vec::new();
+        encode_pfm(&mut data, 1, 2, &pixels).unwrap();
+
+        let header = b"PF\n1 2\n-1.0\n";
+        assert_eq!(&data[..header.len()], header);
+        assert_eq!(data.len(), header.len() + 6 * 4);
+        assert_eq!(&data[header.len()..header.len() + 4], &4.0f32.to_le_bytes());
+    }
+}
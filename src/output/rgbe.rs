@@ -0,0 +1,86 @@
+use crate::tracer::Vec3;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+/// Write rows of pixels (top to bottom) as an uncompressed Radiance .hdr image
+pub fn write_hdr(path: &str, nx: u32, ny: u32, pixels: &[Vec3]) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    encode_hdr(&mut file, nx, ny, pixels)?;
+    file.flush()
+}
+
+pub fn encode_hdr(w: &mut impl Write, nx: u32, ny: u32, pixels: &[Vec3]) -> io::Result<()> {
+    write!(
+        w,
+        "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+        ny, nx
+    )?;
+    for col in pixels {
+        w.write_all(&to_rgbe(*col))?;
+    }
+    Ok(())
+}
+
+/// Pack a color into a shared exponent RGBE pixel
+pub fn to_rgbe(col: Vec3) -> [u8; 4] {
+    let v = col.x.max(col.y).max(col.z);
+    if v < 1e-32 {
+        return [0, 0, 0, 0];
+    }
+    if !v.is_finite() {
+        return [255, 255, 255, 255];
+    }
+
+    // Split v into mantissa * 2^exponent with the mantissa in 0.5..1. Values
+    // too big for the exponent byte saturate.
+    let exponent = (v.log2().floor() as i32 + 1).clamp(-128, 127);
+    let scale = 256.0 / 2f32.powi(exponent);
+    let channel = |c: f32| (c * scale).clamp(0.0, 255.0) as u8;
+    [
+        channel(col.x),
+        channel(col.y),
+        channel(col.z),
+        (exponent + 128) as u8,
+    ]
+}
+
+/// Unpack a shared exponent RGBE pixel
+pub fn from_rgbe(rgbe: [u8; 4]) -> Vec3 {
+    if rgbe[3] == 0 {
+        return Vec3::new(0.0, 0.0, 0.0);
+    }
+
+    let scale = 2f32.powi(rgbe[3] as i32 - 128 - 8);
+    Vec3::new(
+        (rgbe[0] as f32 + 0.5) * scale,
+        (rgbe[1] as f32 + 0.5) * scale,
+        (rgbe[2] as f32 + 0.5) * scale,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rgbe_round_trips_within_precision() {
+        let col = Vec3::new(12.5, 0.75, 3.0);
+        let unpacked = from_rgbe(to_rgbe(col));
+        assert!((unpacked.x - col.x).abs() / col.x < 0.01);
+        assert!((unpacked.y - col.y).abs() < 0.05);
+        assert!((unpacked.z - col.z).abs() / col.z < 0.02);
+    }
+
+    #[test]
+    fn rgbe_black_is_zero() {
+        assert_eq!(to_rgbe(Vec3::new(0.0, 0.0, 0.0)), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn rgbe_saturates_huge_colors() {
+        assert_eq!(to_rgbe(Vec3::new(f32::INFINITY, 1.0, 0.0)), [255; 4]);
+        let rgbe = to_rgbe(Vec3::new(f32::MAX, 0.0, 0.0));
+        assert_eq!((rgbe[0], rgbe[3]), (255, 255));
+        assert!(from_rgbe(rgbe).x > 1e38);
+    }
+}
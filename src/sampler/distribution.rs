@@ -0,0 +1,126 @@
+/// Piecewise constant density over 0..1, sampled by inverting its CDF
+#[derive(Clone, Debug)]
+pub struct Distribution1D {
+    func: Vec<f32>,
+    cdf: Vec<f32>,
+    /// Integral of func over 0..1
+    integral: f32,
+}
+
+impl Distribution1D {
+    /// Density proportional to func, made of equally wide steps. An all zero
+    /// func gives a uniform density.
+    pub fn new(func: Vec<f32>) -> Distribution1D {
+        let n = func.len();
+        let mut cdf = vec![0.0; n + 1];
+        for i in 0..n {
+            cdf[i + 1] = cdf[i] + func[i].max(0.0) / n as f32;
+        }
+        let integral = cdf[n];
+        for (i, value) in cdf.iter_mut().enumerate() {
+            *value = if integral > 0.0 {
+                *value / integral
+            } else {
+                i as f32 / n as f32
+            };
+        }
+
+        Distribution1D {
+            func,
+            cdf,
+            integral,
+        }
+    }
+
+    pub fn integral(&self) -> f32 {
+        self.integral
+    }
+
+    /// Map u to a point in 0..1, returned with the density there and the
+    /// index of its step
+    pub fn sample(&self, u: f32) -> (f32, f32, usize) {
+        let n = self.func.len();
+        // Last step starting at or before u, skipping empty steps
+        let index = (self.cdf.partition_point(|value| *value <= u).max(1) - 1).min(n - 1);
+
+        let width = self.cdf[index + 1] - self.cdf[index];
+        let offset = if width > 0.0 {
+            (u - self.cdf[index]) / width
+        } else {
+            0.0
+        };
+        let x = ((index as f32 + offset) / n as f32).min(1.0 - f32::EPSILON);
+        (x, self.pdf(x), index)
+    }
+
+    /// Density at x in 0..1
+    pub fn pdf(&self, x: f32) -> f32 {
+        if self.integral <= 0.0 {
+            return 1.0;
+        }
+        let n = self.func.len();
+        let index = ((x * n as f32) as usize).min(n - 1);
+        self.func[index].max(0.0) / self.integral
+    }
+}
+
+/// Piecewise constant density over the unit square, sampled a row at a time
+#[derive(Clone, Debug)]
+pub struct Distribution2D {
+    /// Density along each row
+    rows: Vec<Distribution1D>,
+    /// Density of picking each row
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    /// Density proportional to func, given as ny rows of nx values from v = 0
+    /// up
+    pub fn new(func: &[f32], nx: usize, ny: usize) -> Distribution2D {
+        let rows: Vec<Distribution1D> = func
+            .chunks(nx)
+            .take(ny)
+            .map(|row| Distribution1D::new(row.to_vec()))
+            .collect();
+        let marginal = Distribution1D::new(rows.iter().map(Distribution1D::integral).collect());
+        Distribution2D { rows, marginal }
+    }
+
+    pub fn integral(&self) -> f32 {
+        self.marginal.integral()
+    }
+
+    /// Map the 2D sample u to a point (u, v) in the unit square, returned
+    /// with the density there
+    pub fn sample(&self, u: (f32, f32)) -> ((f32, f32), f32) {
+        let (v, pdf_v, row) = self.marginal.sample(u.1);
+        let (u, pdf_u, _) = self.rows[row].sample(u.0);
+        ((u, v), pdf_u * pdf_v)
+    }
+
+    /// Density at (u, v)
+    pub fn pdf(&self, (u, v): (f32, f32)) -> f32 {
+        let ny = self.rows.len();
+        let row = ((v * ny as f32) as usize).min(ny - 1);
+        self.rows[row].pdf(u) * self.marginal.pdf(v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn samples_follow_the_function() {
+        let distribution = Distribution2D::new(&[0.0, 1.0, 3.0, 0.0, 0.0, 0.0], 3, 2);
+        let mut counts = [0; 6];
+        let n = 1000;
+        for i in 0..n {
+            let sample = ((i as f32 + 0.5) / n as f32, (i as f32 * 0.618_034).fract());
+            let ((u, v), pdf) = distribution.sample(sample);
+            assert!((pdf - distribution.pdf((u, v))).abs() < 1e-4);
+            counts[(v * 2.0) as usize * 3 + (u * 3.0) as usize] += 1;
+        }
+        assert_eq!(counts, [0, 250, 750, 0, 0, 0]);
+    }
+}
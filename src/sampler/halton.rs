@@ -0,0 +1,86 @@
+use super::sampling::{bits_to_f32, hash_values, Sampler};
+use crate::tracer::sample_rng;
+use rand::prelude::*;
+
+/// Bases of the Halton dimensions, dimensions past these are uniform random
+const PRIMES: [u32; 32] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131,
+];
+
+/// The Halton sequence, dimension d is the radical inverse of the sample
+/// number in the d-th prime base. Every pixel shifts each dimension by its own
+/// random offset (a Cranley-Patterson rotation), so neighbouring pixels don't
+/// share the same pattern.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct HaltonSampler {
+    seed: u64,
+    pixel: u64,
+    sample: u32,
+    dimension: usize,
+}
+
+impl HaltonSampler {
+    pub fn new() -> HaltonSampler {
+        HaltonSampler::default()
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn start_sample(&mut self, seed: u64, pixel: u64, sample: u32) {
+        self.seed = seed;
+        self.pixel = pixel;
+        self.sample = sample;
+        self.dimension = 0;
+    }
+
+    fn next_1d(&mut self) -> f32 {
+        let dimension = self.dimension;
+        self.dimension += 1;
+        let base = match PRIMES.get(dimension) {
+            Some(base) => *base,
+            None => return sample_rng().gen::<f32>(),
+        };
+
+        let offset = bits_to_f32(hash_values(&[self.seed, self.pixel, dimension as u64]) as u32);
+        let val = radical_inverse(base, self.sample) + offset;
+        // Wrapping can round up to exactly 1.0 in f32
+        (val - val.floor()).min(1.0 - f32::EPSILON)
+    }
+
+    fn next_2d(&mut self) -> (f32, f32) {
+        (self.next_1d(), self.next_1d())
+    }
+
+    fn box_clone(&self) -> Box<dyn Sampler> {
+        Box::new(*self)
+    }
+}
+
+/// Mirror the digits of i in base around the decimal point
+pub fn radical_inverse(base: u32, mut i: u32) -> f32 {
+    let inv_base = 1.0 / f64::from(base);
+    let mut inv_base_n = 1.0;
+    let mut reversed: u64 = 0;
+    while i > 0 {
+        let next = i / base;
+        let digit = i - next * base;
+        reversed = reversed * u64::from(base) + u64::from(digit);
+        inv_base_n *= inv_base;
+        i = next;
+    }
+    ((reversed as f64 * inv_base_n) as f32).min(1.0 - f32::EPSILON)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn radical_inverse_mirrors_digits() {
+        assert_eq!(radical_inverse(2, 0), 0.0);
+        assert_eq!(radical_inverse(2, 1), 0.5);
+        assert_eq!(radical_inverse(2, 6), 0.375);
+        assert!((radical_inverse(3, 5) - 7.0 / 9.0).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,15 @@
+mod distribution;
+mod halton;
+mod random;
+mod sampling;
+mod sobol;
+mod stratified;
+mod warp;
+
+pub use distribution::*;
+pub use halton::*;
+pub use random::*;
+pub use sampling::*;
+pub use sobol::*;
+pub use stratified::*;
+pub use warp::*;
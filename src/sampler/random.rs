@@ -0,0 +1,24 @@
+use super::sampling::Sampler;
+use crate::tracer::sample_rng;
+use rand::prelude::*;
+
+/// Independent uniform random values for every dimension
+#[derive(Copy, Clone, Debug, Default)]
+pub struct RandomSampler;
+
+impl Sampler for RandomSampler {
+    fn start_sample(&mut self, _seed: u64, _pixel: u64, _sample: u32) {}
+
+    fn next_1d(&mut self) -> f32 {
+        sample_rng().gen::<f32>()
+    }
+
+    fn next_2d(&mut self) -> (f32, f32) {
+        let mut rng = sample_rng();
+        (rng.gen::<f32>(), rng.gen::<f32>())
+    }
+
+    fn box_clone(&self) -> Box<dyn Sampler> {
+        Box::new(*self)
+    }
+}
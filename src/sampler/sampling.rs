@@ -0,0 +1,64 @@
+use super::random::RandomSampler;
+use crate::tracer::{hash_u64, seed_sample_rng};
+use std::cell::RefCell;
+
+/// Source of the sample values used to render a pixel. Every random decision
+/// a sample makes (pixel position, lens position, scatter directions...) asks
+/// for the next 1D or 2D dimension, so samplers can spread the values of each
+/// dimension evenly over the samples of a pixel.
+pub trait Sampler: Sync + Send {
+    /// Start sample number sample of pixel
+    fn start_sample(&mut self, seed: u64, pixel: u64, sample: u32);
+    /// Next dimension of the current sample, in 0..1
+    fn next_1d(&mut self) -> f32;
+    /// Next two dimensions of the current sample, in 0..1
+    fn next_2d(&mut self) -> (f32, f32);
+    fn box_clone(&self) -> Box<dyn Sampler>;
+}
+
+pub type BoxSampler = Box<dyn Sampler>;
+
+impl Clone for Box<dyn Sampler> {
+    fn clone(&self) -> Box<dyn Sampler> {
+        self.box_clone()
+    }
+}
+
+thread_local! {
+    static SAMPLER: RefCell<BoxSampler> = RefCell::new(Box::new(RandomSampler));
+}
+
+/// Draw the samples of this thread from a copy of sampler
+pub fn use_sampler(sampler: &BoxSampler) {
+    SAMPLER.with(|current| *current.borrow_mut() = sampler.clone());
+}
+
+/// Start sample number sample of pixel on this thread, reseeding the thread's
+/// sample_rng along with its sampler
+pub fn start_sample(seed: u64, pixel: u64, sample: u32) {
+    seed_sample_rng(seed, pixel, u64::from(sample));
+    SAMPLER.with(|current| current.borrow_mut().start_sample(seed, pixel, sample));
+}
+
+/// Next dimension of the sample being rendered on this thread
+pub fn sample_1d() -> f32 {
+    SAMPLER.with(|current| current.borrow_mut().next_1d())
+}
+
+/// Next two dimensions of the sample being rendered on this thread
+pub fn sample_2d() -> (f32, f32) {
+    SAMPLER.with(|current| current.borrow_mut().next_2d())
+}
+
+/// Hash of a few values, used to give every pixel and dimension its own
+/// scramble
+pub fn hash_values(values: &[u64]) -> u64 {
+    values
+        .iter()
+        .fold(0x2545_f491_4f6c_dd1d, |hash, val| hash_u64(hash ^ val))
+}
+
+/// Convert 32 random bits to a float in 0..1
+pub fn bits_to_f32(bits: u32) -> f32 {
+    (bits >> 8) as f32 / (1u32 << 24) as f32
+}
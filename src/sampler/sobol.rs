@@ -0,0 +1,118 @@
+use super::sampling::{bits_to_f32, hash_values, Sampler};
+
+/// Owen scrambled Sobol sampling, following Burley's "Practical Hash-based
+/// Owen Scrambling" (2020). Every 2D dimension uses the first two Sobol
+/// dimensions, with its own scramble of the points and a shuffle of the
+/// sample order so dimensions don't correlate with each other. Any power of
+/// two count of samples is stratified in every 2D dimension.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SobolSampler {
+    seed: u64,
+    pixel: u64,
+    sample: u32,
+    dimension: u64,
+}
+
+impl SobolSampler {
+    pub fn new() -> SobolSampler {
+        SobolSampler::default()
+    }
+
+    /// Scramble seed of the next dimension
+    fn next_dimension_seed(&mut self) -> u32 {
+        let seed = hash_values(&[self.seed, self.pixel, self.dimension]) as u32;
+        self.dimension += 1;
+        seed
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn start_sample(&mut self, seed: u64, pixel: u64, sample: u32) {
+        self.seed = seed;
+        self.pixel = pixel;
+        self.sample = sample;
+        self.dimension = 0;
+    }
+
+    fn next_1d(&mut self) -> f32 {
+        let seed = self.next_dimension_seed();
+        let index = owen_scramble(self.sample, seed);
+        bits_to_f32(owen_scramble(sobol_0(index), hash_u32(seed)))
+    }
+
+    fn next_2d(&mut self) -> (f32, f32) {
+        let seed = self.next_dimension_seed();
+        let index = owen_scramble(self.sample, seed);
+        let x_seed = hash_u32(seed);
+        let y_seed = hash_u32(x_seed);
+        (
+            bits_to_f32(owen_scramble(sobol_0(index), x_seed)),
+            bits_to_f32(owen_scramble(sobol_1(index), y_seed)),
+        )
+    }
+
+    fn box_clone(&self) -> Box<dyn Sampler> {
+        Box::new(*self)
+    }
+}
+
+/// First Sobol dimension, the van der Corput sequence
+fn sobol_0(index: u32) -> u32 {
+    index.reverse_bits()
+}
+
+/// Second Sobol dimension, from the primitive polynomial x + 1
+fn sobol_1(mut index: u32) -> u32 {
+    let mut direction = 1u32 << 31;
+    let mut result = 0;
+    while index != 0 {
+        if index & 1 != 0 {
+            result ^= direction;
+        }
+        index >>= 1;
+        direction ^= direction >> 1;
+    }
+    result
+}
+
+/// Nested uniform (Owen) scramble of the bits of x. Each bit is flipped based
+/// on a hash of the bits above it.
+fn owen_scramble(x: u32, seed: u32) -> u32 {
+    laine_karras_permutation(x.reverse_bits(), seed).reverse_bits()
+}
+
+/// Hash where each bit only depends on the bits below it
+fn laine_karras_permutation(mut x: u32, seed: u32) -> u32 {
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50_b47c);
+    x ^= x.wrapping_mul(0xb82f_1e52);
+    x ^= x.wrapping_mul(0xc7af_e638);
+    x ^= x.wrapping_mul(0x8d22_f6e6);
+    x
+}
+
+fn hash_u32(x: u32) -> u32 {
+    hash_values(&[u64::from(x)]) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sixteen_samples_cover_every_cell_of_a_4x4_grid() {
+        let mut sampler = SobolSampler::new();
+        for dimension in 0..4 {
+            let mut cells = vec![0; 16];
+            for sample in 0..16 {
+                sampler.start_sample(3, 42, sample);
+                for _ in 0..dimension {
+                    sampler.next_2d();
+                }
+                let (u, v) = sampler.next_2d();
+                cells[(v * 4.0) as usize * 4 + (u * 4.0) as usize] += 1;
+            }
+            assert_eq!(cells, vec![1; 16]);
+        }
+    }
+}
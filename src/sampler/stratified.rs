@@ -0,0 +1,121 @@
+use super::sampling::{hash_values, Sampler};
+use crate::tracer::sample_rng;
+use rand::prelude::*;
+
+/// Jittered stratified sampling. Each dimension is split into one stratum per
+/// sample (a grid of strata for 2D dimensions), the samples of a pixel visit
+/// the strata in a shuffled order and are jittered within them. Samples past
+/// samples_per_pixel start over with a new shuffle.
+#[derive(Copy, Clone, Debug)]
+pub struct StratifiedSampler {
+    pub samples_per_pixel: u32,
+    seed: u64,
+    pixel: u64,
+    sample: u32,
+    dimension: u64,
+}
+
+impl StratifiedSampler {
+    pub fn new(samples_per_pixel: u32) -> StratifiedSampler {
+        StratifiedSampler {
+            samples_per_pixel: samples_per_pixel.max(1),
+            seed: 0,
+            pixel: 0,
+            sample: 0,
+            dimension: 0,
+        }
+    }
+
+    /// Stratum visited by the current sample, out of strata
+    fn stratum(&mut self, strata: u32) -> u32 {
+        let round = self.sample / strata;
+        let scramble = hash_values(&[self.seed, self.pixel, self.dimension, u64::from(round)]);
+        self.dimension += 1;
+        permute(self.sample % strata, strata, scramble as u32)
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn start_sample(&mut self, seed: u64, pixel: u64, sample: u32) {
+        self.seed = seed;
+        self.pixel = pixel;
+        self.sample = sample;
+        self.dimension = 0;
+    }
+
+    fn next_1d(&mut self) -> f32 {
+        let strata = self.samples_per_pixel;
+        let stratum = self.stratum(strata);
+        (stratum as f32 + sample_rng().gen::<f32>()) / strata as f32
+    }
+
+    fn next_2d(&mut self) -> (f32, f32) {
+        let k = (self.samples_per_pixel as f32).sqrt().ceil() as u32;
+        let stratum = self.stratum(k * k);
+        let mut rng = sample_rng();
+        (
+            ((stratum % k) as f32 + rng.gen::<f32>()) / k as f32,
+            ((stratum / k) as f32 + rng.gen::<f32>()) / k as f32,
+        )
+    }
+
+    fn box_clone(&self) -> Box<dyn Sampler> {
+        Box::new(*self)
+    }
+}
+
+/// Element i of a random permutation of 0..len picked by seed, from Kensler's
+/// "Correlated Multi-Jittered Sampling"
+fn permute(mut i: u32, len: u32, seed: u32) -> u32 {
+    let mut w = len - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    loop {
+        i ^= seed;
+        i = i.wrapping_mul(0xe170_893d);
+        i ^= seed >> 16;
+        i ^= (i & w) >> 4;
+        i ^= seed >> 8;
+        i = i.wrapping_mul(0x0929_eb3f);
+        i ^= seed >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | seed >> 27);
+        i = i.wrapping_mul(0x6935_fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dc_b303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e50_1cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860_a3df);
+        i &= w;
+        i ^= i >> 5;
+        // Values past len are walked forward until they land inside it
+        if i < len {
+            break;
+        }
+    }
+    i.wrapping_add(seed) % len
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_stratum_gets_one_sample() {
+        let mut sampler = StratifiedSampler::new(9);
+        let mut strata_1d = vec![0; 9];
+        let mut strata_2d = vec![0; 9];
+        for sample in 0..9 {
+            sampler.start_sample(1, 5, sample);
+            strata_1d[(sampler.next_1d() * 9.0) as usize] += 1;
+            let (u, v) = sampler.next_2d();
+            strata_2d[(v * 3.0) as usize * 3 + (u * 3.0) as usize] += 1;
+        }
+        assert_eq!(strata_1d, vec![1; 9]);
+        assert_eq!(strata_2d, vec![1; 9]);
+    }
+}
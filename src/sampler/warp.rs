@@ -0,0 +1,77 @@
+use crate::tracer::Vec3;
+use std::f32::consts::PI;
+
+/// Map a 2D sample to a point in the unit disk (z = 0). Shirley's concentric
+/// mapping keeps the strata of the sample intact.
+pub fn concentric_disk((u, v): (f32, f32)) -> Vec3 {
+    let a = 2.0 * u - 1.0;
+    let b = 2.0 * v - 1.0;
+    if a == 0.0 && b == 0.0 {
+        return Vec3::new(0.0, 0.0, 0.0);
+    }
+
+    let (r, theta) = if a.abs() > b.abs() {
+        (a, PI / 4.0 * (b / a))
+    } else {
+        (b, PI / 2.0 - PI / 4.0 * (a / b))
+    };
+    Vec3::new(r * theta.cos(), r * theta.sin(), 0.0)
+}
+
+/// Map a 2D sample to a uniformly distributed unit direction
+pub fn uniform_sphere((u, v): (f32, f32)) -> Vec3 {
+    let z = 1.0 - 2.0 * u;
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * PI * v;
+    Vec3::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+/// Map a 2D and a 1D sample to a point uniformly distributed inside the unit
+/// sphere
+pub fn uniform_ball(direction: (f32, f32), radius: f32) -> Vec3 {
+    radius.cbrt() * uniform_sphere(direction)
+}
+
+/// Two unit vectors that form an orthonormal basis with the unit vector w
+/// (Duff et al. 2017)
+pub fn orthonormal_basis(w: Vec3) -> (Vec3, Vec3) {
+    let sign = 1.0f32.copysign(w.z);
+    let a = -1.0 / (sign + w.z);
+    let b = w.x * w.y * a;
+    (
+        Vec3::new(1.0 + sign * w.x * w.x * a, sign * b, -sign * w.x),
+        Vec3::new(b, sign + w.y * w.y * a, -w.y),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn warps_stay_in_bounds() {
+        for i in 0..8 {
+            for j in 0..8 {
+                let sample = (i as f32 / 7.0, j as f32 / 7.0);
+                assert!(concentric_disk(sample).length() <= 1.0 + 1e-5);
+                assert!((uniform_sphere(sample).length() - 1.0).abs() < 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn orthonormal_basis_is_orthonormal() {
+        for w in &[
+            Vec3::new(0.0, 0.0, 1.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            Vec3::new(1.0, 2.0, -3.0).unit_vector(),
+        ] {
+            let (u, v) = orthonormal_basis(*w);
+            assert!((u.length() - 1.0).abs() < 1e-5);
+            assert!((v.length() - 1.0).abs() < 1e-5);
+            assert!(u.dot(&v).abs() < 1e-5);
+            assert!(u.dot(w).abs() < 1e-5);
+            assert!(v.dot(w).abs() < 1e-5);
+        }
+    }
+}
@@ -1,5 +1,5 @@
 use super::texture::Texture;
-use crate::tracer::Vec3;
+use crate::tracer::{HitRecord, Vec3};
 use std::ops::Deref;
 
 #[derive(Clone)]
@@ -21,4 +21,14 @@ impl Texture for CheckerTexture {
     fn box_clone(&self) -> Box<dyn Texture> {
         Box::new(self.deref().clone())
     }
+
+    fn value_at(&self, rec: &HitRecord) -> Vec3 {
+        let p = rec.p;
+        let sines = (p.x * 10.0).sin() * (p.y * 10.0).sin() * (p.z * 10.0).sin();
+        if sines < 0.0 {
+            self.odd.value_at(rec)
+        } else {
+            self.even.value_at(rec)
+        }
+    }
 }
@@ -3,9 +3,11 @@ mod constant_texture;
 mod image_texture;
 mod noise_texture;
 mod texture;
+mod vertex_color_texture;
 
 pub use checker_texture::*;
 pub use constant_texture::*;
 pub use image_texture::*;
 pub use noise_texture::*;
 pub use texture::*;
+pub use vertex_color_texture::*;
@@ -1,5 +1,5 @@
 use super::texture::Texture;
-use crate::tracer::Vec3;
+use crate::tracer::{Pcg32, Vec3};
 use lazy_static;
 use rand::{prelude::*, seq::SliceRandom};
 use std::ops::Deref;
@@ -38,12 +38,15 @@ struct Perlin {
 }
 
 impl Perlin {
+    /// The tables are generated from a fixed seed, so noise textures look the
+    /// same on every run
     fn new() -> Perlin {
+        let mut rng = Pcg32::new(PERLIN_SEED, 0);
         Perlin {
-            rand_vec3: Self::gen_rand_vec3_list(),
-            x: Self::gen_rand_int_list(),
-            y: Self::gen_rand_int_list(),
-            z: Self::gen_rand_int_list(),
+            rand_vec3: Self::gen_rand_vec3_list(&mut rng),
+            x: Self::gen_rand_int_list(&mut rng),
+            y: Self::gen_rand_int_list(&mut rng),
+            z: Self::gen_rand_int_list(&mut rng),
         }
     }
 
@@ -101,9 +104,8 @@ impl Perlin {
         accum.abs()
     }
 
-    fn gen_rand_vec3_list() -> [Vec3; 256] {
+    fn gen_rand_vec3_list(rng: &mut Pcg32) -> [Vec3; 256] {
         let mut p: [Vec3; 256] = [Vec3::new(0.0, 0.0, 0.0); 256];
-        let mut rng = thread_rng();
         for i in 0..256 {
             p[i] = Vec3::new(
                 -1.0 + 2.0 * rng.gen::<f32>(),
@@ -115,21 +117,22 @@ impl Perlin {
         p
     }
 
-    fn permute(p: &mut [i32; 256]) {
-        let mut rng = thread_rng();
-        p.shuffle(&mut rng);
+    fn permute(p: &mut [i32; 256], rng: &mut Pcg32) {
+        p.shuffle(rng);
     }
 
-    fn gen_rand_int_list() -> [i32; 256] {
+    fn gen_rand_int_list(rng: &mut Pcg32) -> [i32; 256] {
         let mut p: [i32; 256] = [0; 256];
         for i in 0..256 {
             p[i] = i as i32;
         }
-        Self::permute(&mut p);
+        Self::permute(&mut p, rng);
         p
     }
 }
 
+const PERLIN_SEED: u64 = 0x5eed;
+
 lazy_static! {
     static ref PERLIN: Perlin = Perlin::new();
 }
@@ -1,10 +1,16 @@
-use crate::tracer::Vec3;
+use crate::tracer::{HitRecord, Vec3};
 use std::ops::Deref;
 use std::sync::Arc;
 
 pub trait Texture: Sync + Send {
     fn value(&self, u: f32, v: f32, p: Vec3) -> Vec3;
     fn box_clone(&self) -> Box<dyn Texture>;
+
+    /// Color at a hit, looked up by its uv and point unless the texture
+    /// needs more of the hit
+    fn value_at(&self, rec: &HitRecord) -> Vec3 {
+        self.value(rec.u, rec.v, rec.p)
+    }
 }
 
 pub type BoxTexture = Box<dyn Texture>;
@@ -17,6 +23,10 @@ impl<T: Texture> Texture for Box<T> {
     fn box_clone(&self) -> Box<dyn Texture> {
         self.deref().box_clone()
     }
+
+    fn value_at(&self, rec: &HitRecord) -> Vec3 {
+        self.deref().value_at(rec)
+    }
 }
 
 impl Clone for Box<dyn Texture> {
@@ -33,4 +43,8 @@ impl<T: Texture> Texture for Arc<T> {
     fn box_clone(&self) -> Box<dyn Texture> {
         self.deref().box_clone()
     }
+
+    fn value_at(&self, rec: &HitRecord) -> Vec3 {
+        self.deref().value_at(rec)
+    }
 }
@@ -0,0 +1,21 @@
+use super::texture::Texture;
+use crate::tracer::{HitRecord, Vec3};
+
+/// The colors of a mesh's vertices, blended across each triangle. Hits on
+/// anything without vertex colors are white.
+#[derive(Copy, Clone)]
+pub struct VertexColorTexture;
+
+impl Texture for VertexColorTexture {
+    fn value(&self, _u: f32, _v: f32, _p: Vec3) -> Vec3 {
+        Vec3::new(1.0, 1.0, 1.0)
+    }
+
+    fn box_clone(&self) -> Box<dyn Texture> {
+        Box::new(*self)
+    }
+
+    fn value_at(&self, rec: &HitRecord) -> Vec3 {
+        rec.vertex_color.unwrap_or_else(|| Vec3::new(1.0, 1.0, 1.0))
+    }
+}
@@ -0,0 +1,150 @@
+use super::checkpoint::PixelSamples;
+use super::vec3::Vec3;
+
+/// Keeps the error estimate of pixels that are nearly black finite
+const MIN_LUMINANCE: f32 = 1e-4;
+
+/// Stop sampling pixels once their noise drops below a threshold. Every pixel
+/// gets at least min_samples, so the noise estimate can be trusted, and no
+/// pixel gets more than max_samples.
+#[derive(Copy, Clone, Debug)]
+pub struct AdaptiveSampling {
+    pub min_samples: u32,
+    pub max_samples: u32,
+    /// Largest acceptable standard error of a pixel, measured in gamma 2
+    /// display values (0..1)
+    pub threshold: f32,
+    /// Also save an image showing how many samples each pixel took, next to
+    /// the render with a -spp suffix
+    pub sample_count_image: bool,
+}
+
+impl Default for AdaptiveSampling {
+    fn default() -> AdaptiveSampling {
+        AdaptiveSampling {
+            min_samples: 16,
+            max_samples: 1024,
+            threshold: 0.005,
+            sample_count_image: false,
+        }
+    }
+}
+
+impl AdaptiveSampling {
+    /// Which pixels of an nx by ny image need no more samples. A pixel is only
+    /// done when its neighbours are too, since a pixel whose first samples all
+    /// missed the light looks noise free while the pixels around it don't.
+    pub fn done_pixels(&self, nx: u32, ny: u32, pixels: &[PixelSamples]) -> Vec<bool> {
+        let errors: Vec<f32> = pixels.iter().map(pixel_error).collect();
+        let (nx, ny) = (nx as i32, ny as i32);
+
+        let mut done = Vec::with_capacity(pixels.len());
+        for y in 0..ny {
+            for x in 0..nx {
+                let pixel = &pixels[(y * nx + x) as usize];
+                if pixel.count >= self.max_samples {
+                    done.push(true);
+                    continue;
+                }
+                if pixel.count < self.min_samples {
+                    done.push(false);
+                    continue;
+                }
+
+                let mut error: f32 = 0.0;
+                for qy in (y - 1).max(0)..(y + 2).min(ny) {
+                    for qx in (x - 1).max(0)..(x + 2).min(nx) {
+                        error = error.max(errors[(qy * nx + qx) as usize]);
+                    }
+                }
+                done.push(error <= self.threshold);
+            }
+        }
+        done
+    }
+}
+
+/// Estimated standard error of the pixel's mean, as seen after gamma 2.
+/// Displayed values are about sqrt(L), so an error dL in luminance shows up
+/// as dL / (2 sqrt(L)), which lets dark pixels stop at a larger absolute
+/// error than bright ones, just like the eye tolerates.
+pub fn pixel_error(pixel: &PixelSamples) -> f32 {
+    if pixel.count < 2 {
+        return f32::INFINITY;
+    }
+
+    let n = pixel.count as f32;
+    let mean = luminance(pixel.color) / n;
+    let variance = ((pixel.luminance_sq - n * mean * mean) / (n - 1.0)).max(0.0);
+    let standard_error = (variance / n).sqrt();
+    standard_error / (2.0 * mean.max(MIN_LUMINANCE).sqrt())
+}
+
+/// Rec. 709 relative luminance of a linear color
+pub fn luminance(col: Vec3) -> f32 {
+    0.2126 * col.x + 0.7152 * col.y + 0.0722 * col.z
+}
+
+/// Heat map of sample counts, from black through red and yellow up to white
+/// at max_samples
+pub fn sample_count_display(counts: &[u32], max_samples: u32) -> Vec<Vec3> {
+    counts
+        .iter()
+        .map(|count| {
+            let t = 3.0 * *count as f32 / max_samples.max(1) as f32;
+            Vec3::new(
+                t.min(1.0),
+                (t - 1.0).clamp(0.0, 1.0),
+                (t - 2.0).clamp(0.0, 1.0),
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pixel_with(samples: &[f32]) -> PixelSamples {
+        let mut pixel = PixelSamples::default();
+        for l in samples {
+            pixel.add(&PixelSamples::sample(
+                Vec3::new(*l, *l, *l),
+                Default::default(),
+            ));
+        }
+        pixel
+    }
+
+    #[test]
+    fn constant_pixels_converge_after_min_samples() {
+        let adaptive = AdaptiveSampling::default();
+        let pixels = vec![pixel_with(&[0.0; 8]), pixel_with(&[0.5; 8])];
+        assert_eq!(adaptive.done_pixels(2, 1, &pixels), vec![false, false]);
+
+        let pixels = vec![pixel_with(&[0.0; 16]), pixel_with(&[0.5; 16])];
+        assert_eq!(adaptive.done_pixels(2, 1, &pixels), vec![true, true]);
+    }
+
+    #[test]
+    fn noisy_pixels_keep_themselves_and_neighbours_sampling() {
+        let adaptive = AdaptiveSampling {
+            max_samples: 64,
+            ..AdaptiveSampling::default()
+        };
+        let noise: Vec<f32> = (0..32).map(|s| (s % 2) as f32).collect();
+        let pixels = vec![
+            pixel_with(&noise),
+            pixel_with(&[0.0; 32]),
+            pixel_with(&[0.0; 32]),
+        ];
+        assert_eq!(
+            adaptive.done_pixels(3, 1, &pixels),
+            vec![false, false, true]
+        );
+
+        let noise: Vec<f32> = (0..64).map(|s| (s % 2) as f32).collect();
+        let pixels = vec![pixel_with(&noise), pixel_with(&[0.0; 32])];
+        assert_eq!(adaptive.done_pixels(2, 1, &pixels), vec![true, false]);
+    }
+}
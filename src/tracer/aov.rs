@@ -0,0 +1,133 @@
+use super::framebuffer::Features;
+use super::vec3::Vec3;
+use crate::output::ExrChannel;
+
+/// Arbitrary output variables, per pixel surface data from the first hit of
+/// each camera ray that can be written out next to the render
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Aov {
+    Albedo,
+    /// Shading normal
+    Normal,
+    /// Distance from the camera
+    Depth,
+    /// World space position
+    Position,
+    /// Id set with Translation::with_id
+    ObjectId,
+}
+
+impl Aov {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Aov::Albedo => "albedo",
+            Aov::Normal => "normal",
+            Aov::Depth => "depth",
+            Aov::Position => "position",
+            Aov::ObjectId => "object_id",
+        }
+    }
+
+    /// Raw value of the pass, single value passes are repeated across x, y and z
+    pub fn value(&self, features: &Features) -> Vec3 {
+        match self {
+            Aov::Albedo => features.albedo,
+            Aov::Normal => features.normal,
+            Aov::Depth => Vec3::new(features.depth, features.depth, features.depth),
+            Aov::Position => features.position,
+            Aov::ObjectId => {
+                let id = features.object_id as f32;
+                Vec3::new(id, id, id)
+            }
+        }
+    }
+
+    /// Remap raw pass values to 0..1 so they can be viewed as an 8 bit image
+    pub fn to_display(&self, values: &[Vec3]) -> Vec<Vec3> {
+        match self {
+            Aov::Albedo => values.to_vec(),
+            Aov::Normal => values
+                .iter()
+                .map(|n| 0.5 * *n + Vec3::new(0.5, 0.5, 0.5))
+                .collect(),
+            Aov::Depth => {
+                let max = values.iter().fold(0.0f32, |max, d| max.max(d.x));
+                let max = if max > 0.0 { max } else { 1.0 };
+                values.iter().map(|d| *d / max).collect()
+            }
+            Aov::Position => {
+                let (min, max) = bounds(values);
+                let extent = max - min;
+                values
+                    .iter()
+                    .map(|p| {
+                        Vec3::new(
+                            (p.x - min.x) / extent.x.max(1e-6),
+                            (p.y - min.y) / extent.y.max(1e-6),
+                            (p.z - min.z) / extent.z.max(1e-6),
+                        )
+                    })
+                    .collect()
+            }
+            Aov::ObjectId => values.iter().map(|id| id_color(id.x as u32)).collect(),
+        }
+    }
+
+    /// Channels of the pass in a layered EXR image
+    pub fn exr_channels(&self, values: &[Vec3]) -> Vec<ExrChannel> {
+        let channel = |name: &str, component: fn(&Vec3) -> f32| ExrChannel {
+            name: name.to_string(),
+            samples: values.iter().map(component).collect(),
+        };
+
+        match self {
+            Aov::Albedo => vec![
+                channel("albedo.R", |v| v.x),
+                channel("albedo.G", |v| v.y),
+                channel("albedo.B", |v| v.z),
+            ],
+            Aov::Normal => vec![
+                channel("normal.X", |v| v.x),
+                channel("normal.Y", |v| v.y),
+                channel("normal.Z", |v| v.z),
+            ],
+            Aov::Depth => vec![channel("Z", |v| v.x)],
+            Aov::Position => vec![
+                channel("position.X", |v| v.x),
+                channel("position.Y", |v| v.y),
+                channel("position.Z", |v| v.z),
+            ],
+            Aov::ObjectId => vec![channel("object_id", |v| v.x)],
+        }
+    }
+}
+
+fn bounds(values: &[Vec3]) -> (Vec3, Vec3) {
+    let mut min = Vec3::new(f32::MAX, f32::MAX, f32::MAX);
+    let mut max = Vec3::new(-f32::MAX, -f32::MAX, -f32::MAX);
+    for v in values {
+        min = Vec3::new(min.x.min(v.x), min.y.min(v.y), min.z.min(v.z));
+        max = Vec3::new(max.x.max(v.x), max.y.max(v.y), max.z.max(v.z));
+    }
+    (min, max)
+}
+
+/// A stable, easy to tell apart color for each id. Id 0 is black.
+fn id_color(id: u32) -> Vec3 {
+    if id == 0 {
+        return Vec3::new(0.0, 0.0, 0.0);
+    }
+
+    // Stepping the hue by the golden ratio keeps consecutive ids far apart
+    let hue = (id as f32 * 0.618_034).fract() * 6.0;
+    let x = 1.0 - (hue % 2.0 - 1.0).abs();
+    let (r, g, b) = match hue as u32 {
+        0 => (1.0, x, 0.0),
+        1 => (x, 1.0, 0.0),
+        2 => (0.0, 1.0, x),
+        3 => (0.0, x, 1.0),
+        4 => (x, 0.0, 1.0),
+        _ => (1.0, 0.0, x),
+    };
+    Vec3::new(r, g, b)
+}
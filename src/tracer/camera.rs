@@ -1,5 +1,6 @@
 use super::ray::Ray;
 use super::vec3::Vec3;
+use crate::sampler::{concentric_disk, sample_2d};
 use std::f32::consts::PI;
 
 #[derive(Copy, Clone, Debug)]
@@ -58,7 +59,7 @@ impl Camera {
     }
 
     pub fn get_ray(&self, s: f32, t: f32) -> Ray {
-        let rd = self.lens_radius * Vec3::random_in_unit_disk();
+        let rd = self.lens_radius * concentric_disk(sample_2d());
         let offset = self.u * rd.x + self.v * rd.y;
         Ray {
             origin: self.origin + offset,
@@ -0,0 +1,295 @@
+use super::adaptive::luminance;
+use super::error::RenderError;
+use super::framebuffer::{Features, Framebuffer};
+use super::vec3::Vec3;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::time::Duration;
+
+const MAGIC: &[u8; 4] = b"RTCK";
+const VERSION: u32 = 3;
+/// Bytes each pixel takes up after the 16 byte header
+const PIXEL_SIZE: usize = 80;
+
+/// Sums of every sample taken in a pixel so far
+#[derive(Copy, Clone, Debug, Default)]
+pub struct PixelSamples {
+    /// Sum of the colors of the samples taken in this pixel
+    pub color: Vec3,
+    /// Sum of the filter weighted colors of every sample that reaches this
+    /// pixel, including samples taken in neighbouring pixels
+    pub filtered: Vec3,
+    /// Sum of the filter weights of those samples
+    pub weight: f32,
+    pub features: Features,
+    /// Sum of the squared luminance of each sample, used to estimate noise
+    pub luminance_sq: f32,
+    pub count: u32,
+}
+
+impl PixelSamples {
+    /// A single sample
+    pub fn sample(color: Vec3, features: Features) -> PixelSamples {
+        let l = luminance(color);
+        PixelSamples {
+            color,
+            features,
+            luminance_sq: l * l,
+            count: 1,
+            ..PixelSamples::default()
+        }
+    }
+
+    pub fn add(&mut self, other: &PixelSamples) {
+        if self.count == 0 {
+            self.features.object_id = other.features.object_id;
+        }
+        self.color += other.color;
+        self.filtered += other.filtered;
+        self.weight += other.weight;
+        self.features.add(&other.features);
+        self.luminance_sq += other.luminance_sq;
+        self.count += other.count;
+    }
+
+    /// Reconstructed color of the pixel. Falls back to the plain average of
+    /// the pixel's samples when negative filter lobes cancel out the weights.
+    pub fn color(&self) -> Vec3 {
+        if self.weight > 1e-6 {
+            return self.filtered / self.weight;
+        }
+        self.color / self.count.max(1) as f32
+    }
+
+    /// Average features of the samples
+    pub fn features(&self) -> Features {
+        self.features.scale(1.0 / self.count.max(1) as f32)
+    }
+}
+
+/// Where and how often a progressive render saves its state
+#[derive(Clone, Debug)]
+pub struct CheckpointOpts {
+    pub path: String,
+    /// Minimum time between checkpoints, they are only written between passes
+    pub interval: Duration,
+}
+
+/// The accumulated samples of a progressive render, everything needed to
+/// pick the render back up where it left off
+#[derive(Clone, Debug)]
+pub struct Checkpoint {
+    pub nx: u32,
+    pub ny: u32,
+    /// Rows of pixels from the top of the image down
+    pub pixels: Vec<PixelSamples>,
+}
+
+impl Checkpoint {
+    /// An empty checkpoint, where no samples have been taken
+    pub fn new(nx: u32, ny: u32) -> Checkpoint {
+        Checkpoint {
+            nx,
+            ny,
+            pixels: vec![PixelSamples::default(); nx as usize * ny as usize],
+        }
+    }
+
+    /// The fewest samples taken in any pixel
+    pub fn min_samples(&self) -> u32 {
+        self.pixels
+            .iter()
+            .map(|pixel| pixel.count)
+            .min()
+            .unwrap_or(0)
+    }
+
+    /// Average the samples into a framebuffer, including features if asked
+    pub fn to_framebuffer(&self, with_features: bool) -> Framebuffer {
+        let mut framebuffer = Framebuffer::new(self.nx, self.ny);
+        framebuffer.pixels = self.pixels.iter().map(|pixel| pixel.color()).collect();
+        framebuffer.sample_counts = self.pixels.iter().map(|pixel| pixel.count).collect();
+        if with_features {
+            framebuffer.features = Some(self.pixels.iter().map(|pixel| pixel.features()).collect());
+        }
+        framebuffer
+    }
+
+    /// Save to path. The checkpoint is written next to path first and then
+    /// moved over it, so an interrupted save never loses the last checkpoint.
+    pub fn save(&self, path: &str) -> Result<(), RenderError> {
+        let temp_path = format!("{}.tmp", path);
+        {
+            let mut file = BufWriter::new(File::create(&temp_path)?);
+            file.write_all(&self.encode())?;
+            file.flush()?;
+        }
+        fs::rename(&temp_path, path)?;
+        Ok(())
+    }
+
+    pub fn load(path: &str) -> Result<Checkpoint, RenderError> {
+        let mut data = Vec::new();
+        BufReader::new(File::open(path)?).read_to_end(&mut data)?;
+        Checkpoint::decode(&data)
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(16 + self.pixels.len() * PIXEL_SIZE);
+        data.extend(MAGIC);
+        for val in &[VERSION, self.nx, self.ny] {
+            data.extend(&val.to_le_bytes());
+        }
+
+        for pixel in &self.pixels {
+            let features = &pixel.features;
+            data.extend(&pixel.count.to_le_bytes());
+            data.extend(&features.object_id.to_le_bytes());
+            for vec in &[
+                pixel.color,
+                pixel.filtered,
+                features.albedo,
+                features.normal,
+                features.position,
+            ] {
+                for val in &[vec.x, vec.y, vec.z] {
+                    data.extend(&val.to_le_bytes());
+                }
+            }
+            data.extend(&features.depth.to_le_bytes());
+            data.extend(&pixel.luminance_sq.to_le_bytes());
+            data.extend(&pixel.weight.to_le_bytes());
+        }
+        data
+    }
+
+    fn decode(data: &[u8]) -> Result<Checkpoint, RenderError> {
+        let mut reader = Reader { data, pos: 0 };
+        if reader.take(4)? != MAGIC {
+            return Err(RenderError::Checkpoint("not a checkpoint file".into()));
+        }
+
+        let version = reader.u32()?;
+        if version != VERSION {
+            return Err(RenderError::Checkpoint(format!(
+                "unsupported checkpoint version {}",
+                version
+            )));
+        }
+
+        let nx = reader.u32()?;
+        let ny = reader.u32()?;
+        // Check the size before trusting it to allocate
+        let size = (nx as usize)
+            .checked_mul(ny as usize)
+            .and_then(|pixels| pixels.checked_mul(PIXEL_SIZE))
+            .and_then(|size| size.checked_add(16));
+        if size != Some(data.len()) {
+            return Err(RenderError::Checkpoint(
+                "checkpoint file has the wrong size".into(),
+            ));
+        }
+        let mut pixels = Vec::with_capacity(nx as usize * ny as usize);
+        for _ in 0..nx as usize * ny as usize {
+            let count = reader.u32()?;
+            let object_id = reader.u32()?;
+            let color = reader.vec3()?;
+            let filtered = reader.vec3()?;
+            let albedo = reader.vec3()?;
+            let normal = reader.vec3()?;
+            let position = reader.vec3()?;
+            let depth = reader.f32()?;
+            let luminance_sq = reader.f32()?;
+            let weight = reader.f32()?;
+            pixels.push(PixelSamples {
+                color,
+                filtered,
+                weight,
+                features: Features {
+                    albedo,
+                    normal,
+                    depth,
+                    position,
+                    object_id,
+                },
+                luminance_sq,
+                count,
+            });
+        }
+
+        Ok(Checkpoint { nx, ny, pixels })
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], RenderError> {
+        if self.pos + len > self.data.len() {
+            return Err(RenderError::Checkpoint(
+                "checkpoint file is truncated".into(),
+            ));
+        }
+        let bytes = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, RenderError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn f32(&mut self) -> Result<f32, RenderError> {
+        Ok(f32::from_bits(self.u32()?))
+    }
+
+    fn vec3(&mut self) -> Result<Vec3, RenderError> {
+        Ok(Vec3::new(self.f32()?, self.f32()?, self.f32()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checkpoint_round_trips() {
+        let mut checkpoint = Checkpoint::new(2, 1);
+        checkpoint.pixels[1] = PixelSamples {
+            color: Vec3::new(1.0, 2.0, 3.0),
+            features: Features {
+                depth: 4.5,
+                object_id: 7,
+                ..Features::default()
+            },
+            luminance_sq: 8.0,
+            count: 12,
+            ..PixelSamples::default()
+        };
+
+        let decoded = Checkpoint::decode(&checkpoint.encode()).unwrap();
+        assert_eq!((decoded.nx, decoded.ny), (2, 1));
+        assert_eq!(decoded.min_samples(), 0);
+        assert_eq!(decoded.pixels[1].count, 12);
+        assert_eq!(decoded.pixels[1].color.z, 3.0);
+        assert_eq!(decoded.pixels[1].features.depth, 4.5);
+        assert_eq!(decoded.pixels[1].features.object_id, 7);
+        assert_eq!(decoded.pixels[1].luminance_sq, 8.0);
+    }
+
+    #[test]
+    fn decode_rejects_truncated_files() {
+        let data = Checkpoint::new(4, 4).encode();
+        assert!(Checkpoint::decode(&data[..data.len() - 1]).is_err());
+
+        let mut huge = MAGIC.to_vec();
+        for val in &[VERSION, u32::MAX, u32::MAX] {
+            huge.extend(&val.to_le_bytes());
+        }
+        assert!(Checkpoint::decode(&huge).is_err());
+    }
+}
@@ -0,0 +1,65 @@
+use super::adaptive::luminance;
+use super::vec3::Vec3;
+
+/// Color of light given off by a black body at kelvin degrees, in linear
+/// sRGB and scaled to a luminance of 1. 6500 K is close to white, lower is
+/// redder and higher is bluer.
+pub fn blackbody(kelvin: f32) -> Vec3 {
+    // Below this the spectrum's visible part is too dim for f32
+    let kelvin = kelvin.max(500.0);
+
+    let mut xyz = Vec3::new(0.0, 0.0, 0.0);
+    for step in 0..=80 {
+        let wavelength = 380.0 + 5.0 * step as f32;
+        // Planck's law with the wavelength in micrometers, up to a constant
+        let micrometers = wavelength / 1000.0;
+        let exponent = 14388.0 / (micrometers * kelvin);
+        xyz += color_matching(wavelength) / (micrometers.powi(5) * (exponent.exp() - 1.0));
+    }
+
+    let rgb = xyz_to_rgb(xyz / xyz.y);
+    rgb / luminance(rgb).max(1e-8)
+}
+
+/// CIE 1931 color matching functions at wavelength in nanometers, using the
+/// multi-lobe fit from Wyman, Sloan and Shirley (2013)
+fn color_matching(wavelength: f32) -> Vec3 {
+    let lobe = |mean: f32, below: f32, above: f32| {
+        let width = if wavelength < mean { below } else { above };
+        let t = (wavelength - mean) / width;
+        (-0.5 * t * t).exp()
+    };
+    Vec3::new(
+        1.056 * lobe(599.8, 37.9, 31.0) + 0.362 * lobe(442.0, 16.0, 26.7)
+            - 0.065 * lobe(501.1, 20.4, 26.2),
+        0.821 * lobe(568.8, 46.9, 40.5) + 0.286 * lobe(530.9, 16.3, 31.1),
+        1.217 * lobe(437.0, 11.8, 36.0) + 0.681 * lobe(459.0, 26.0, 13.8),
+    )
+}
+
+/// CIE XYZ to linear sRGB, with colors outside the gamut clamped to it
+pub(crate) fn xyz_to_rgb(xyz: Vec3) -> Vec3 {
+    Vec3::new(
+        (3.2406 * xyz.x - 1.5372 * xyz.y - 0.4986 * xyz.z).max(0.0),
+        (-0.9689 * xyz.x + 1.8758 * xyz.y + 0.0415 * xyz.z).max(0.0),
+        (0.0557 * xyz.x - 0.2040 * xyz.y + 1.0570 * xyz.z).max(0.0),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blackbody_goes_from_red_to_blue() {
+        let warm = blackbody(2000.0);
+        let white = blackbody(6500.0);
+        let cool = blackbody(15000.0);
+        for color in &[warm, white, cool] {
+            assert!((luminance(*color) - 1.0).abs() < 1e-4);
+        }
+        assert!(warm.x > 2.0 * warm.z);
+        assert!(cool.z > cool.x);
+        assert!((white.x - white.z).abs() < 0.15, "{}", white);
+    }
+}
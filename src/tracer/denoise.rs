@@ -0,0 +1,171 @@
+use super::framebuffer::Features;
+use super::vec3::Vec3;
+
+/// B3 spline weights of the 5x5 à-trous kernel
+const KERNEL: [f32; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+
+/// Keeps pixels with black albedo from dividing by zero when demodulating
+const ALBEDO_EPSILON: f32 = 0.001;
+
+/// Edge avoiding à-trous wavelet filter (Dammertz et al. 2010). The filter
+/// blurs across pixels with similar albedo, normal and depth, so noise is
+/// smoothed out without blurring over the edges of objects or textures.
+#[derive(Copy, Clone, Debug)]
+pub struct Denoiser {
+    /// Number of filter passes, each pass doubles the reach of the filter
+    pub iterations: u32,
+    /// How different the (tone compressed) lighting of two pixels may be
+    pub sigma_color: f32,
+    pub sigma_normal: f32,
+    /// Relative depth difference tolerated between two pixels
+    pub sigma_depth: f32,
+    pub sigma_albedo: f32,
+}
+
+impl Default for Denoiser {
+    fn default() -> Denoiser {
+        Denoiser {
+            iterations: 5,
+            sigma_color: 2.0,
+            sigma_normal: 0.3,
+            sigma_depth: 0.05,
+            sigma_albedo: 0.1,
+        }
+    }
+}
+
+impl Denoiser {
+    /// Denoise rows of pixels (top to bottom) using the matching features
+    pub fn denoise(&self, nx: u32, ny: u32, pixels: &[Vec3], features: &[Features]) -> Vec<Vec3> {
+        // Filter lighting rather than color, so texture detail is not blurred away
+        let mut illumination: Vec<Vec3> = pixels
+            .iter()
+            .zip(features)
+            .map(|(col, features)| *col / demodulation_albedo(features.albedo))
+            .collect();
+
+        let mut sigma_color = self.sigma_color;
+        for iteration in 0..self.iterations {
+            illumination =
+                self.filter_pass(nx, ny, &illumination, features, 1 << iteration, sigma_color);
+            // Later passes average over wider areas and so see less noise
+            sigma_color *= 0.5;
+        }
+
+        illumination
+            .iter()
+            .zip(features)
+            .map(|(col, features)| *col * demodulation_albedo(features.albedo))
+            .collect()
+    }
+
+    fn filter_pass(
+        &self,
+        nx: u32,
+        ny: u32,
+        illumination: &[Vec3],
+        features: &[Features],
+        step: i32,
+        sigma_color: f32,
+    ) -> Vec<Vec3> {
+        let (nx, ny) = (nx as i32, ny as i32);
+        let mut filtered = Vec::with_capacity(illumination.len());
+
+        for y in 0..ny {
+            for x in 0..nx {
+                let p = (y * nx + x) as usize;
+                let col_p = compress(illumination[p]);
+                let features_p = &features[p];
+
+                let mut sum = Vec3::new(0.0, 0.0, 0.0);
+                let mut weight_sum = 0.0;
+                for (ky, ky_weight) in KERNEL.iter().enumerate() {
+                    for (kx, kx_weight) in KERNEL.iter().enumerate() {
+                        let qx = x + (kx as i32 - 2) * step;
+                        let qy = y + (ky as i32 - 2) * step;
+                        if qx < 0 || qx >= nx || qy < 0 || qy >= ny {
+                            continue;
+                        }
+
+                        let q = (qy * nx + qx) as usize;
+                        let features_q = &features[q];
+                        let color_dist = (col_p - compress(illumination[q])).squared_length();
+                        let normal_dist = (features_p.normal - features_q.normal).squared_length();
+                        let albedo_dist = (features_p.albedo - features_q.albedo).squared_length();
+                        let depth_dist = (features_p.depth - features_q.depth).abs()
+                            / features_p.depth.max(features_q.depth).max(1e-4);
+
+                        let weight = kx_weight
+                            * ky_weight
+                            * (-color_dist / (sigma_color * sigma_color)
+                                - normal_dist / (self.sigma_normal * self.sigma_normal)
+                                - albedo_dist / (self.sigma_albedo * self.sigma_albedo)
+                                - depth_dist / self.sigma_depth)
+                                .exp();
+                        sum += weight * illumination[q];
+                        weight_sum += weight;
+                    }
+                }
+
+                // The center pixel always has a weight, so weight_sum is never zero
+                filtered.push(sum / weight_sum);
+            }
+        }
+        filtered
+    }
+}
+
+fn demodulation_albedo(albedo: Vec3) -> Vec3 {
+    Vec3::new(
+        albedo.x.max(ALBEDO_EPSILON),
+        albedo.y.max(ALBEDO_EPSILON),
+        albedo.z.max(ALBEDO_EPSILON),
+    )
+}
+
+/// Compress high dynamic range values so bright pixels don't dominate the
+/// color distance
+fn compress(col: Vec3) -> Vec3 {
+    Vec3::new(
+        col.x / (1.0 + col.x),
+        col.y / (1.0 + col.y),
+        col.z / (1.0 + col.z),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn denoise_smooths_noise_but_keeps_albedo_edges() {
+        let (nx, ny) = (16, 16);
+        let mut pixels = Vec::new();
+        let mut features = Vec::new();
+        for y in 0..ny {
+            for x in 0..nx {
+                // Left half is a dark surface, right half is bright. Lighting is a
+                // constant 0.5 with a checkerboard of noise on top.
+                let albedo = if x < nx / 2 { 0.2 } else { 0.8 };
+                let noise = if (x + y) % 2 == 0 { 0.1 } else { -0.1 };
+                let col = albedo * (0.5 + noise);
+                pixels.push(Vec3::new(col, col, col));
+                features.push(Features {
+                    albedo: Vec3::new(albedo, albedo, albedo),
+                    normal: Vec3::new(0.0, 0.0, 1.0),
+                    depth: 10.0,
+                    ..Features::default()
+                });
+            }
+        }
+
+        let denoised = Denoiser::default().denoise(nx, ny, &pixels, &features);
+        let at = |x: u32, y: u32| denoised[(y * nx + x) as usize].x;
+        assert!((at(3, 7) - 0.1).abs() < 0.01);
+        assert!((at(4, 7) - 0.1).abs() < 0.01);
+        assert!((at(12, 7) - 0.4).abs() < 0.01);
+        // Pixels right beside the edge must not bleed into each other
+        assert!((at(7, 7) - 0.1).abs() < 0.02);
+        assert!((at(8, 7) - 0.4).abs() < 0.02);
+    }
+}
@@ -0,0 +1,252 @@
+use super::adaptive::luminance;
+use super::sky::Sky;
+use super::vec3::Vec3;
+use crate::sampler::Distribution2D;
+use crate::utils::read_hdr_image;
+use std::f32::consts::PI;
+use std::io;
+use std::sync::Arc;
+
+/// Light arriving from a direction, given as a unit vector
+pub type EnvironmentFn = Arc<dyn Fn(Vec3) -> Vec3 + Sync + Send>;
+
+/// Light coming from infinitely far away, seen by rays that miss the world
+#[derive(Clone)]
+pub enum Environment {
+    /// The same light from every direction, black by default
+    Constant(Vec3),
+    /// Blends from bottom straight down to top straight up
+    Gradient {
+        bottom: Vec3,
+        top: Vec3,
+    },
+    Custom(EnvironmentFn),
+    /// Image based lighting, sampled directly as a light
+    Map(EnvironmentMap),
+    /// Daylight sky and sun, sampled directly as a light
+    Sky(Sky),
+}
+
+impl Default for Environment {
+    fn default() -> Environment {
+        Environment::Constant(Vec3::new(0.0, 0.0, 0.0))
+    }
+}
+
+impl Environment {
+    /// White to light blue sky from the first book
+    pub fn sky() -> Environment {
+        Environment::Gradient {
+            bottom: Vec3::new(1.0, 1.0, 1.0),
+            top: Vec3::new(0.5, 0.7, 1.0),
+        }
+    }
+
+    /// Wrap a closure over unit directions
+    pub fn custom<F>(f: F) -> Environment
+    where
+        F: Fn(Vec3) -> Vec3 + Sync + Send + 'static,
+    {
+        Environment::Custom(Arc::new(f))
+    }
+
+    /// Light arriving along the reverse of direction
+    pub fn radiance(&self, direction: Vec3) -> Vec3 {
+        let direction = direction.unit_vector();
+        match self {
+            Environment::Constant(color) => *color,
+            Environment::Gradient { bottom, top } => {
+                let t = 0.5 * (direction.y + 1.0);
+                (1.0 - t) * *bottom + t * *top
+            }
+            Environment::Custom(f) => f(direction),
+            Environment::Map(map) => map.radiance(direction),
+            Environment::Sky(sky) => sky.radiance(direction),
+        }
+    }
+
+    /// Whether lights can be sampled from the environment
+    pub fn is_light(&self) -> bool {
+        matches!(self, Environment::Map(_) | Environment::Sky(_))
+    }
+
+    /// Pick a unit direction to sample light from, returned with its solid
+    /// angle density. None for environments that aren't lights.
+    pub fn sample(&self, u: (f32, f32)) -> Option<(Vec3, f32)> {
+        match self {
+            Environment::Map(map) => map.sample(u),
+            Environment::Sky(sky) => sky.sample(u),
+            _ => None,
+        }
+    }
+
+    /// Solid angle density with which sample picks direction
+    pub fn pdf(&self, direction: Vec3) -> f32 {
+        match self {
+            Environment::Map(map) => map.pdf(direction),
+            Environment::Sky(sky) => sky.pdf(direction),
+            _ => 0.0,
+        }
+    }
+}
+
+/// Lat-long (equirectangular) image wrapped around the scene, with +y up and
+/// the image's center looking down -z
+#[derive(Clone)]
+pub struct EnvironmentMap {
+    /// Turn around the vertical axis in degrees
+    pub rotation: f32,
+    /// Scale applied to the image's colors
+    pub intensity: f32,
+    pixels: Arc<Vec<Vec3>>,
+    nx: usize,
+    ny: usize,
+    /// Brightness of every pixel times the solid angle it covers
+    distribution: Arc<Distribution2D>,
+}
+
+impl EnvironmentMap {
+    /// Rows of linear colors from the top of the image down. Panics if the
+    /// image is empty or pixels doesn't hold nx * ny colors.
+    pub fn new(pixels: Vec<Vec3>, nx: u32, ny: u32) -> EnvironmentMap {
+        let (nx, ny) = (nx as usize, ny as usize);
+        assert!(nx > 0 && ny > 0, "environment maps can't be empty");
+        assert_eq!(pixels.len(), nx * ny, "environment map size doesn't match");
+        let mut func = Vec::with_capacity(nx * ny);
+        for y in 0..ny {
+            // Rows get narrower towards the poles
+            let sin_theta = (PI * (y as f32 + 0.5) / ny as f32).sin();
+            for x in 0..nx {
+                func.push(luminance(pixels[y * nx + x]) * sin_theta);
+            }
+        }
+
+        EnvironmentMap {
+            rotation: 0.0,
+            intensity: 1.0,
+            distribution: Arc::new(Distribution2D::new(&func, nx, ny)),
+            pixels: Arc::new(pixels),
+            nx,
+            ny,
+        }
+    }
+
+    /// Load a lat-long .hdr or .exr image
+    pub fn open(path: &str) -> io::Result<EnvironmentMap> {
+        let (pixels, nx, ny) = read_hdr_image(path)?;
+        if nx == 0 || ny == 0 || pixels.len() != nx as usize * ny as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} is an empty or truncated image", path),
+            ));
+        }
+        Ok(EnvironmentMap::new(pixels, nx, ny))
+    }
+
+    /// Light arriving along the reverse of direction
+    pub fn radiance(&self, direction: Vec3) -> Vec3 {
+        let (u, v) = self.direction_to_uv(direction);
+        let x = ((u * self.nx as f32) as usize).min(self.nx - 1);
+        let y = ((v * self.ny as f32) as usize).min(self.ny - 1);
+        self.intensity * self.pixels[y * self.nx + x]
+    }
+
+    /// Pick a unit direction, with brighter parts of the map more likely,
+    /// returned with its solid angle density
+    pub fn sample(&self, u: (f32, f32)) -> Option<(Vec3, f32)> {
+        if self.distribution.integral() <= 0.0 {
+            return None;
+        }
+
+        let (uv, pdf) = self.distribution.sample(u);
+        let sin_theta = (PI * uv.1).sin();
+        if pdf <= 0.0 || sin_theta <= 0.0 {
+            return None;
+        }
+        Some((self.uv_to_direction(uv), pdf / (2.0 * PI * PI * sin_theta)))
+    }
+
+    /// Solid angle density with which sample picks direction
+    pub fn pdf(&self, direction: Vec3) -> f32 {
+        if self.distribution.integral() <= 0.0 {
+            return 0.0;
+        }
+
+        let uv = self.direction_to_uv(direction);
+        let sin_theta = (PI * uv.1).sin();
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+        self.distribution.pdf(uv) / (2.0 * PI * PI * sin_theta)
+    }
+
+    fn direction_to_uv(&self, direction: Vec3) -> (f32, f32) {
+        let d = self.rotate(direction.unit_vector(), -self.rotation);
+        let u = 0.5 + d.x.atan2(-d.z) / (2.0 * PI);
+        let v = d.y.clamp(-1.0, 1.0).acos() / PI;
+        (u, v)
+    }
+
+    fn uv_to_direction(&self, (u, v): (f32, f32)) -> Vec3 {
+        let phi = 2.0 * PI * (u - 0.5);
+        let theta = PI * v;
+        let d = Vec3::new(
+            theta.sin() * phi.sin(),
+            theta.cos(),
+            -theta.sin() * phi.cos(),
+        );
+        self.rotate(d, self.rotation)
+    }
+
+    /// Turn v by degrees around the y axis
+    fn rotate(&self, v: Vec3, degrees: f32) -> Vec3 {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        Vec3::new(cos * v.x + sin * v.z, v.y, -sin * v.x + cos * v.z)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gradient_blends_from_bottom_to_top() {
+        let sky = Environment::sky();
+        let up = sky.radiance(Vec3::new(0.0, 2.0, 0.0));
+        let down = sky.radiance(Vec3::new(0.0, -1.0, 0.0));
+        assert_eq!((up.x, up.y, up.z), (0.5, 0.7, 1.0));
+        assert_eq!((down.x, down.y, down.z), (1.0, 1.0, 1.0));
+
+        let custom = Environment::custom(|direction| direction);
+        assert_eq!(custom.radiance(Vec3::new(0.0, 0.0, -3.0)).z, -1.0);
+    }
+
+    #[test]
+    fn map_samples_match_the_pdf_and_integrate_to_one() {
+        let nx = 16;
+        let ny = 8;
+        let pixels: Vec<Vec3> = (0..nx * ny)
+            .map(|i| {
+                let brightness = if i == 37 { 50.0 } else { 0.1 * (i % 5) as f32 };
+                Vec3::new(brightness, brightness, brightness)
+            })
+            .collect();
+        let mut map = EnvironmentMap::new(pixels, nx as u32, ny as u32);
+        map.rotation = 30.0;
+
+        let (direction, pdf) = map.sample((0.3, 0.6)).unwrap();
+        assert!((pdf - map.pdf(direction)).abs() < 1e-3 * pdf);
+        assert!(map.radiance(direction).x > 0.0);
+
+        let n = 400;
+        let mut integral = 0.0;
+        for i in 0..n {
+            for j in 0..n {
+                let u = ((i as f32 + 0.5) / n as f32, (j as f32 + 0.5) / n as f32);
+                integral += map.pdf(crate::sampler::uniform_sphere(u));
+            }
+        }
+        integral *= 4.0 * PI / (n * n) as f32;
+        assert!((integral - 1.0).abs() < 0.02, "{}", integral);
+    }
+}
@@ -0,0 +1,54 @@
+use image::ImageError;
+use std::{error::Error, fmt, io};
+
+#[derive(Debug)]
+pub enum RenderError {
+    /// The scene has settings that can't be rendered, such as a zero width
+    InvalidScene(String),
+    /// A render thread panicked before finishing its tiles
+    ThreadPanicked,
+    /// AOVs were requested from a framebuffer rendered without features
+    MissingFeatures,
+    /// A checkpoint file is malformed or doesn't match the scene
+    Checkpoint(String),
+    Io(io::Error),
+    Image(ImageError),
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RenderError::InvalidScene(reason) => write!(f, "Invalid scene: {}", reason),
+            RenderError::ThreadPanicked => write!(f, "A render thread panicked"),
+            RenderError::MissingFeatures => write!(
+                f,
+                "The framebuffer has no features, render with aovs or a denoiser set"
+            ),
+            RenderError::Checkpoint(reason) => write!(f, "Checkpoint error: {}", reason),
+            RenderError::Io(e) => write!(f, "IO error: {}", e),
+            RenderError::Image(e) => write!(f, "Image error: {}", e),
+        }
+    }
+}
+
+impl Error for RenderError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RenderError::Io(e) => Some(e),
+            RenderError::Image(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for RenderError {
+    fn from(e: io::Error) -> RenderError {
+        RenderError::Io(e)
+    }
+}
+
+impl From<ImageError> for RenderError {
+    fn from(e: ImageError) -> RenderError {
+        RenderError::Image(e)
+    }
+}
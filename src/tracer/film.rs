@@ -0,0 +1,107 @@
+use super::checkpoint::PixelSamples;
+use super::filter::Filter;
+use super::tile::Tile;
+use super::vec3::Vec3;
+
+/// Samples gathered while rendering a tile. Filtered samples reach past the
+/// edges of the tile, so the film covers the tile grown by the filter's reach.
+pub struct FilmTile {
+    /// Pixels covered by the film, clipped to the image
+    pub bounds: Tile,
+    /// Rows of pixels from the top of bounds down
+    pub pixels: Vec<PixelSamples>,
+    filter: Filter,
+}
+
+impl FilmTile {
+    pub fn new(tile: &Tile, nx: i32, ny: i32, filter: Filter) -> FilmTile {
+        let reach = filter.pixel_reach();
+        let bounds = Tile {
+            x0: (tile.x0 - reach).max(0),
+            y0: (tile.y0 - reach).max(0),
+            x1: (tile.x1 + reach).min(nx),
+            y1: (tile.y1 + reach).min(ny),
+        };
+        FilmTile {
+            bounds,
+            pixels: vec![PixelSamples::default(); (bounds.width() * bounds.height()) as usize],
+            filter,
+        }
+    }
+
+    /// Add the samples taken in pixel (x, y)
+    pub fn add_samples(&mut self, x: i32, y: i32, samples: &PixelSamples) {
+        let index = self.index(x, y);
+        self.pixels[index].add(samples);
+    }
+
+    /// Add a sample at (sx, y) in continuous pixel coordinates, measured from
+    /// the top left corner of the image, to every pixel the filter reaches
+    pub fn splat(&mut self, sx: f32, sy: f32, color: Vec3) {
+        let radius = self.filter.radius();
+        // Pixels whose centers lie in (s - radius, s + radius]
+        let x0 = ((sx - radius - 0.5).floor() as i32 + 1).max(self.bounds.x0);
+        let x1 = ((sx + radius - 0.5).floor() as i32).min(self.bounds.x1 - 1);
+        let y0 = ((sy - radius - 0.5).floor() as i32 + 1).max(self.bounds.y0);
+        let y1 = ((sy + radius - 0.5).floor() as i32).min(self.bounds.y1 - 1);
+
+        for y in y0..=y1 {
+            for x in x0..=x1 {
+                let weight = self
+                    .filter
+                    .evaluate(sx - (x as f32 + 0.5), sy - (y as f32 + 0.5));
+                let index = self.index(x, y);
+                let pixel = &mut self.pixels[index];
+                pixel.filtered += weight * color;
+                pixel.weight += weight;
+            }
+        }
+    }
+
+    fn index(&self, x: i32, y: i32) -> usize {
+        ((y - self.bounds.y0) * self.bounds.width() + x - self.bounds.x0) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn box_filter_only_splats_into_the_sampled_pixel() {
+        let tile = Tile {
+            x0: 0,
+            y0: 0,
+            x1: 2,
+            y1: 2,
+        };
+        let mut film = FilmTile::new(&tile, 2, 2, Filter::default());
+        film.splat(1.0, 0.0, Vec3::new(1.0, 1.0, 1.0));
+        film.splat(1.99, 0.5, Vec3::new(1.0, 1.0, 1.0));
+        let weights: Vec<f32> = film.pixels.iter().map(|pixel| pixel.weight).collect();
+        assert_eq!(weights, vec![0.0, 2.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn wide_filters_splat_past_the_tile() {
+        let tile = Tile {
+            x0: 2,
+            y0: 2,
+            x1: 4,
+            y1: 4,
+        };
+        let mut film = FilmTile::new(&tile, 8, 8, Filter::Tent { radius: 1.5 });
+        assert_eq!(
+            (
+                film.bounds.x0,
+                film.bounds.y0,
+                film.bounds.x1,
+                film.bounds.y1
+            ),
+            (1, 1, 5, 5)
+        );
+
+        film.splat(2.1, 2.1, Vec3::new(1.0, 1.0, 1.0));
+        assert!(film.pixels[0].weight > 0.0);
+    }
+}
@@ -0,0 +1,135 @@
+use std::f32::consts::PI;
+
+/// Pixel reconstruction filter. Every sample is added to all pixels whose
+/// centers lie within radius of it (in pixels), weighted by the filter.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Filter {
+    /// Equal weights. With a radius of 0.5 every sample only counts towards
+    /// the pixel it was taken in.
+    Box { radius: f32 },
+    /// Weights fall off linearly to 0 at radius
+    Tent { radius: f32 },
+    /// Gaussian with falloff alpha, shifted to reach 0 at radius
+    Gaussian { radius: f32, alpha: f32 },
+    /// Mitchell-Netravali cubic, b = c = 1 / 3 is the recommended balance of
+    /// blur and ringing
+    Mitchell { radius: f32, b: f32, c: f32 },
+    /// Sinc windowed by a wider sinc, the sharpest of the filters
+    Lanczos { radius: f32 },
+}
+
+impl Default for Filter {
+    fn default() -> Filter {
+        Filter::Box { radius: 0.5 }
+    }
+}
+
+impl Filter {
+    pub fn gaussian(radius: f32) -> Filter {
+        Filter::Gaussian { radius, alpha: 2.0 }
+    }
+
+    pub fn mitchell(radius: f32) -> Filter {
+        Filter::Mitchell {
+            radius,
+            b: 1.0 / 3.0,
+            c: 1.0 / 3.0,
+        }
+    }
+
+    pub fn radius(&self) -> f32 {
+        match self {
+            Filter::Box { radius }
+            | Filter::Tent { radius }
+            | Filter::Gaussian { radius, .. }
+            | Filter::Mitchell { radius, .. }
+            | Filter::Lanczos { radius } => *radius,
+        }
+    }
+
+    /// Weight of a sample at offset (dx, dy) in pixels from a pixel's center.
+    /// Mitchell and Lanczos have negative lobes, so weights can be negative.
+    pub fn evaluate(&self, dx: f32, dy: f32) -> f32 {
+        self.evaluate_1d(dx) * self.evaluate_1d(dy)
+    }
+
+    fn evaluate_1d(&self, x: f32) -> f32 {
+        let radius = self.radius();
+        let x = x.abs();
+        if x > radius {
+            return 0.0;
+        }
+
+        match *self {
+            Filter::Box { .. } => 1.0,
+            Filter::Tent { .. } => radius - x,
+            Filter::Gaussian { alpha, .. } => {
+                ((-alpha * x * x).exp() - (-alpha * radius * radius).exp()).max(0.0)
+            }
+            Filter::Mitchell { b, c, .. } => mitchell_1d(2.0 * x / radius, b, c),
+            Filter::Lanczos { .. } => sinc(x) * sinc(x / radius),
+        }
+    }
+
+    /// Farthest, in whole pixels, that a sample can land from the pixels it
+    /// counts towards
+    pub fn pixel_reach(&self) -> i32 {
+        ((self.radius() + 0.5).ceil() as i32 - 1).max(0)
+    }
+}
+
+/// Mitchell-Netravali cubic over x in 0..2
+fn mitchell_1d(x: f32, b: f32, c: f32) -> f32 {
+    let weight = if x > 1.0 {
+        (-b - 6.0 * c) * x * x * x
+            + (6.0 * b + 30.0 * c) * x * x
+            + (-12.0 * b - 48.0 * c) * x
+            + (8.0 * b + 24.0 * c)
+    } else {
+        (12.0 - 9.0 * b - 6.0 * c) * x * x * x
+            + (-18.0 + 12.0 * b + 6.0 * c) * x * x
+            + (6.0 - 2.0 * b)
+    };
+    weight / 6.0
+}
+
+fn sinc(x: f32) -> f32 {
+    if x < 1e-5 {
+        return 1.0;
+    }
+    (PI * x).sin() / (PI * x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filters_peak_at_center_and_vanish_past_radius() {
+        let filters = [
+            Filter::default(),
+            Filter::Tent { radius: 1.0 },
+            Filter::gaussian(1.5),
+            Filter::mitchell(2.0),
+            Filter::Lanczos { radius: 3.0 },
+        ];
+        for filter in &filters {
+            let center = filter.evaluate(0.0, 0.0);
+            assert!(center > 0.0);
+            assert!(filter.evaluate(0.3, 0.1) <= center);
+            assert_eq!(filter.evaluate(filter.radius() + 0.01, 0.0), 0.0);
+        }
+    }
+
+    #[test]
+    fn mitchell_has_negative_lobes() {
+        assert!(Filter::mitchell(2.0).evaluate(1.5, 0.0) < 0.0);
+    }
+
+    #[test]
+    fn pixel_reach_covers_the_radius() {
+        assert_eq!(Filter::default().pixel_reach(), 0);
+        assert_eq!(Filter::Tent { radius: 1.0 }.pixel_reach(), 1);
+        assert_eq!(Filter::mitchell(2.0).pixel_reach(), 2);
+    }
+}
@@ -0,0 +1,228 @@
+use super::adaptive::sample_count_display;
+use super::aov::Aov;
+use super::error::RenderError;
+use super::tone_map::ToneMap;
+use super::vec3::Vec3;
+use crate::output::{rgb_channels, write_exr, write_exr_channels, write_hdr, write_pfm};
+use image;
+use std::path::Path;
+
+/// Surface properties where camera rays first hit the scene, averaged over
+/// the samples of a pixel. Rays that miss contribute zeros.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Features {
+    pub albedo: Vec3,
+    pub normal: Vec3,
+    /// Distance from the camera along the ray
+    pub depth: f32,
+    pub position: Vec3,
+    /// Ids can't be averaged, so this is the id hit by the pixel's first sample
+    pub object_id: u32,
+}
+
+impl Features {
+    /// Add other's averageable features to self
+    pub fn add(&mut self, other: &Features) {
+        self.albedo += other.albedo;
+        self.normal += other.normal;
+        self.depth += other.depth;
+        self.position += other.position;
+    }
+
+    pub fn scale(&self, s: f32) -> Features {
+        Features {
+            albedo: self.albedo * s,
+            normal: self.normal * s,
+            depth: self.depth * s,
+            position: self.position * s,
+            object_id: self.object_id,
+        }
+    }
+}
+
+/// Linear float RGB pixels, stored in rows from the top of the image down
+#[derive(Clone, Debug)]
+pub struct Framebuffer {
+    pub nx: u32,
+    pub ny: u32,
+    pub pixels: Vec<Vec3>,
+    /// Per pixel features, only collected when the render needs them
+    pub features: Option<Vec<Features>>,
+    /// Number of samples taken in each pixel
+    pub sample_counts: Vec<u32>,
+}
+
+impl Framebuffer {
+    /// Create a black framebuffer
+    pub fn new(nx: u32, ny: u32) -> Framebuffer {
+        Framebuffer {
+            nx,
+            ny,
+            pixels: vec![Vec3::new(0.0, 0.0, 0.0); nx as usize * ny as usize],
+            features: None,
+            sample_counts: vec![0; nx as usize * ny as usize],
+        }
+    }
+
+    /// Pixel at column x, row y (counted from the top)
+    pub fn get(&self, x: u32, y: u32) -> Vec3 {
+        self.pixels[self.index(x, y)]
+    }
+
+    pub fn set(&mut self, x: u32, y: u32, col: Vec3) {
+        let index = self.index(x, y);
+        self.pixels[index] = col;
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        y as usize * self.nx as usize + x as usize
+    }
+
+    /// Tone map and quantize to 8 bit RGB
+    pub fn to_rgb8(&self, tone_map: &ToneMap) -> Vec<u8> {
+        let mut data = Vec::with_capacity(self.pixels.len() * 3);
+        for col in &self.pixels {
+            let col = tone_map.apply(*col);
+            data.push(to_u8(col.x));
+            data.push(to_u8(col.y));
+            data.push(to_u8(col.z));
+        }
+        data
+    }
+
+    /// Save to path, picking the format from its extension. .pfm, .hdr and .exr
+    /// files keep the full float values, anything else is tone mapped to 8 bit.
+    pub fn save(&self, path: &str, tone_map: &ToneMap) -> Result<(), RenderError> {
+        save_pixels(path, self.nx, self.ny, &self.pixels, |pixels| {
+            pixels.iter().map(|col| tone_map.apply(*col)).collect()
+        })
+    }
+
+    /// Values of an AOV pass for every pixel
+    pub fn aov(&self, aov: Aov) -> Result<Vec<Vec3>, RenderError> {
+        let features = self.features.as_ref().ok_or(RenderError::MissingFeatures)?;
+        Ok(features
+            .iter()
+            .map(|features| aov.value(features))
+            .collect())
+    }
+
+    /// Save a single AOV pass, float formats get the raw values and 8 bit
+    /// formats get values remapped for viewing
+    pub fn save_aov(&self, aov: Aov, path: &str) -> Result<(), RenderError> {
+        save_pixels(path, self.nx, self.ny, &self.aov(aov)?, |values| {
+            aov.to_display(values)
+        })
+    }
+
+    /// Save the number of samples taken in each pixel. Float formats get the
+    /// raw counts, 8 bit formats a heat map that is white at max_samples.
+    pub fn save_sample_counts(&self, path: &str, max_samples: u32) -> Result<(), RenderError> {
+        let counts: Vec<Vec3> = self
+            .sample_counts
+            .iter()
+            .map(|count| {
+                let count = *count as f32;
+                Vec3::new(count, count, count)
+            })
+            .collect();
+        save_pixels(path, self.nx, self.ny, &counts, |_| {
+            sample_count_display(&self.sample_counts, max_samples)
+        })
+    }
+
+    /// Save the render and the given AOV passes as layers of one OpenEXR image
+    pub fn save_layered_exr(&self, path: &str, aovs: &[Aov]) -> Result<(), RenderError> {
+        let mut channels = rgb_channels("", &self.pixels);
+        for aov in aovs {
+            channels.extend(aov.exr_channels(&self.aov(*aov)?));
+        }
+        write_exr_channels(path, self.nx, self.ny, channels)?;
+        Ok(())
+    }
+}
+
+/// Write pixels in the format picked from path's extension, using to_display
+/// to bring values into 0..1 for 8 bit formats
+fn save_pixels(
+    path: &str,
+    nx: u32,
+    ny: u32,
+    pixels: &[Vec3],
+    to_display: impl FnOnce(&[Vec3]) -> Vec<Vec3>,
+) -> Result<(), RenderError> {
+    match extension(path).as_deref() {
+        Some("pfm") => write_pfm(path, nx, ny, pixels)?,
+        Some("hdr") => write_hdr(path, nx, ny, pixels)?,
+        Some("exr") => write_exr(path, nx, ny, pixels)?,
+        _ => {
+            let mut data = Vec::with_capacity(pixels.len() * 3);
+            for col in to_display(pixels) {
+                data.push(to_u8(col.x));
+                data.push(to_u8(col.y));
+                data.push(to_u8(col.z));
+            }
+            image::save_buffer(path, &data, nx, ny, image::ColorType::RGB(8))?
+        }
+    };
+    Ok(())
+}
+
+/// Lowercased extension of path
+pub fn extension(path: &str) -> Option<String> {
+    Path::new(path)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_lowercase())
+}
+
+/// Path for an AOV written next to the render at path, i.e. image-albedo.png
+pub fn aov_path(path: &str, aov: Aov) -> String {
+    suffixed_path(path, aov.name())
+}
+
+/// Path with -suffix added to the file name, before the extension
+pub fn suffixed_path(path: &str, suffix: &str) -> String {
+    let path = Path::new(path);
+    let stem = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or_default();
+    let file_name = match path.extension().and_then(|extension| extension.to_str()) {
+        Some(extension) => format!("{}-{}.{}", stem, suffix, extension),
+        None => format!("{}-{}", stem, suffix),
+    };
+    path.with_file_name(file_name)
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Convert a display referred value in 0..1 to 0..255
+pub fn to_u8(val: f32) -> u8 {
+    (255.99 * val).clamp(0.0, 255.0) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn to_rgb8_defaults_to_gamma_2_and_clamps() {
+        let mut fb = Framebuffer::new(2, 1);
+        fb.set(0, 0, Vec3::new(0.25, 1.0, 0.0));
+        fb.set(1, 0, Vec3::new(4.0, -1.0, 0.01));
+        assert_eq!(
+            fb.to_rgb8(&ToneMap::default()),
+            vec![127, 255, 0, 255, 0, 25]
+        );
+    }
+
+    #[test]
+    fn aov_path_adds_pass_name() {
+        assert_eq!(
+            aov_path("./out/frame-1.png", Aov::Depth),
+            "./out/frame-1-depth.png"
+        );
+        assert_eq!(aov_path("render", Aov::Albedo), "render-albedo");
+    }
+}
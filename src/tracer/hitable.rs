@@ -1,6 +1,8 @@
+use super::super::light::LightBounds;
 use super::super::material::Material;
 use super::bounding_box::BoundingBox;
 use super::ray::Ray;
+use super::transform::Transform;
 use super::vec3::Vec3;
 use std::ops::Deref;
 use std::sync::Arc;
@@ -10,6 +12,46 @@ pub trait Hitable: Sync + Send {
     /// If t0 and t1 inside the hitable's box it will return (tmin, tmax)
     fn bounding_box(&self) -> Option<BoundingBox>;
     fn box_clone(&self) -> Box<dyn Hitable>;
+
+    /// Direction from origin to a random point on the hitable, picked with
+    /// the 2D sample u. None for hitables that can't be sampled.
+    fn sample(&self, _origin: Vec3, _u: (f32, f32)) -> Option<Vec3> {
+        None
+    }
+
+    /// Solid angle density, as seen from origin, with which sample picks
+    /// direction
+    fn pdf(&self, _origin: Vec3, _direction: Vec3) -> f32 {
+        0.0
+    }
+
+    /// The emissive parts of the hitable, in world space, that can be sampled
+    /// directly as lights. Emissive hitables must support sample and pdf.
+    fn lights(&self) -> Vec<BoxHitable> {
+        Vec::new()
+    }
+
+    /// Surface area, used to spread a light's power over it. 0 for hitables
+    /// without a surface.
+    fn area(&self) -> f32 {
+        0.0
+    }
+
+    /// Surface area once moved by transform. Flat surfaces work it out
+    /// exactly, the default is exact for uniform scales and only a guess
+    /// when curved surfaces are stretched.
+    fn transformed_area(&self, transform: &Transform) -> f32 {
+        match transform.uniform_scale() {
+            Some(scale) => self.area() * scale * scale,
+            None => self.area() * transform.determinant().abs().powf(2.0 / 3.0),
+        }
+    }
+
+    /// Where and how bright the hitable is as a light, used to pick which
+    /// lights to sample. None for hitables that don't give off light.
+    fn light_bounds(&self) -> Option<LightBounds> {
+        None
+    }
 }
 
 impl Clone for Box<dyn Hitable> {
@@ -28,6 +70,10 @@ pub struct HitRecord<'a> {
     pub p: Vec3,
     pub normal: Vec3,
     pub material: &'a Material,
+    /// Set with Translation::with_id, 0 for untagged hitables
+    pub object_id: u32,
+    /// Color blended from the vertex colors of meshes that have them
+    pub vertex_color: Option<Vec3>,
 }
 
 impl Hitable for Arc<dyn Hitable> {
@@ -39,8 +85,33 @@ impl Hitable for Arc<dyn Hitable> {
         self.deref().bounding_box()
     }
 
+    /// Shares the hitable rather than copying it
     fn box_clone(&self) -> Box<dyn Hitable> {
-        self.deref().box_clone()
+        Box::new(self.clone())
+    }
+
+    fn sample(&self, origin: Vec3, u: (f32, f32)) -> Option<Vec3> {
+        self.deref().sample(origin, u)
+    }
+
+    fn pdf(&self, origin: Vec3, direction: Vec3) -> f32 {
+        self.deref().pdf(origin, direction)
+    }
+
+    fn lights(&self) -> Vec<BoxHitable> {
+        self.deref().lights()
+    }
+
+    fn area(&self) -> f32 {
+        self.deref().area()
+    }
+
+    fn transformed_area(&self, transform: &Transform) -> f32 {
+        self.deref().transformed_area(transform)
+    }
+
+    fn light_bounds(&self) -> Option<LightBounds> {
+        self.deref().light_bounds()
     }
 }
 
@@ -56,4 +127,28 @@ impl Hitable for Box<dyn Hitable> {
     fn box_clone(&self) -> Box<dyn Hitable> {
         self.deref().box_clone()
     }
+
+    fn sample(&self, origin: Vec3, u: (f32, f32)) -> Option<Vec3> {
+        self.deref().sample(origin, u)
+    }
+
+    fn pdf(&self, origin: Vec3, direction: Vec3) -> f32 {
+        self.deref().pdf(origin, direction)
+    }
+
+    fn lights(&self) -> Vec<BoxHitable> {
+        self.deref().lights()
+    }
+
+    fn area(&self) -> f32 {
+        self.deref().area()
+    }
+
+    fn transformed_area(&self, transform: &Transform) -> f32 {
+        self.deref().transformed_area(transform)
+    }
+
+    fn light_bounds(&self) -> Option<LightBounds> {
+        self.deref().light_bounds()
+    }
 }
@@ -0,0 +1,251 @@
+use super::framebuffer::Features;
+use super::hitable::{HitRecord, Hitable};
+use super::ray::Ray;
+use super::scene::Scene;
+use super::vec3::Vec3;
+use crate::light::LightList;
+use crate::material::BounceKind;
+use crate::sampler::{sample_1d, sample_2d};
+
+/// Highest chance a path survives Russian roulette, so even bright paths
+/// eventually end
+const MAX_SURVIVAL: f32 = 0.95;
+
+/// Weight of a sample taken with density pdf, combined by multiple importance
+/// sampling with another strategy that would have picked it with other_pdf
+fn power_heuristic(pdf: f32, other_pdf: f32) -> f32 {
+    let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+    if a + b <= 0.0 {
+        return 0.0;
+    }
+    a / (a + b)
+}
+
+/// Shadow rays stop this fraction short of the light, so they don't hit it
+const SHADOW_SHORTENING: f32 = 1e-3;
+
+/// Most bounces a path may take, in total and of each kind
+#[derive(Copy, Clone, Debug)]
+pub struct MaxDepth {
+    pub total: u32,
+    /// Bounces off diffuse surfaces
+    pub diffuse: u32,
+    /// Bounces off mirrors, metals and glass
+    pub specular: u32,
+    /// Scatters inside participating media
+    pub volume: u32,
+}
+
+impl Default for MaxDepth {
+    fn default() -> MaxDepth {
+        MaxDepth {
+            total: 50,
+            diffuse: 50,
+            specular: 50,
+            volume: 50,
+        }
+    }
+}
+
+/// Bounces a path has taken so far
+#[derive(Copy, Clone, Debug, Default)]
+struct Bounces {
+    total: u32,
+    diffuse: u32,
+    specular: u32,
+    volume: u32,
+}
+
+impl Bounces {
+    /// Whether another bounce of kind stays within max_depth
+    fn allows(&self, kind: BounceKind, max_depth: &MaxDepth) -> bool {
+        let (count, max) = match kind {
+            BounceKind::Diffuse => (self.diffuse, max_depth.diffuse),
+            BounceKind::Specular => (self.specular, max_depth.specular),
+            BounceKind::Volume => (self.volume, max_depth.volume),
+        };
+        self.total < max_depth.total && count < max
+    }
+
+    fn add(&mut self, kind: BounceKind) {
+        self.total += 1;
+        match kind {
+            BounceKind::Diffuse => self.diffuse += 1,
+            BounceKind::Specular => self.specular += 1,
+            BounceKind::Volume => self.volume += 1,
+        }
+    }
+}
+
+/// Light arriving along r, traced as a single path. Lights are reached both
+/// by sampling them directly and by bounces hitting them, and the two are
+/// weighted by multiple importance sampling. features are filled in from the
+/// first hit when given.
+pub fn radiance(
+    r: &Ray,
+    scene: &Scene,
+    lights: &LightList,
+    mut features: Option<&mut Features>,
+) -> Vec3 {
+    let mut ray = *r;
+    let mut radiance = Vec3::new(0.0, 0.0, 0.0);
+    // Fraction of the light at the current vertex that makes it to the camera
+    let mut throughput = Vec3::new(1.0, 1.0, 1.0);
+    let mut bounces = Bounces::default();
+    // Density with which the last bounce picked ray's direction, when lights
+    // were sampled there as well
+    let mut scatter_pdf: Option<f32> = None;
+    // Normal at the last bounce, none inside volumes
+    let mut scatter_normal: Option<Vec3> = None;
+
+    loop {
+        let rec = match scene.world.hit(&ray, 0.001, f32::MAX) {
+            Some(rec) => rec,
+            None => {
+                let weight = match scatter_pdf {
+                    Some(pdf) => {
+                        let light_pdf = lights.pdf(ray.origin, scatter_normal, ray.direction, None);
+                        power_heuristic(pdf, light_pdf)
+                    }
+                    None => 1.0,
+                };
+                radiance += throughput * scene.environment.radiance(ray.direction) * weight;
+                break;
+            }
+        };
+
+        if let Some(features) = features.take() {
+            features.albedo = rec.material.albedo(&rec);
+            features.normal = rec.normal;
+            features.depth = rec.t * ray.direction.length();
+            features.position = rec.p;
+            features.object_id = rec.object_id;
+        }
+
+        if rec.material.is_emissive() {
+            let weight = match scatter_pdf {
+                Some(pdf) => {
+                    let light_pdf =
+                        lights.pdf(ray.origin, scatter_normal, ray.direction, Some(rec.p));
+                    power_heuristic(pdf, light_pdf)
+                }
+                None => 1.0,
+            };
+            radiance += throughput * rec.material.emitted(&ray, &rec) * weight;
+        }
+
+        let kind = rec.material.bounce_kind();
+        let normal = match kind {
+            BounceKind::Volume => None,
+            _ => Some(rec.normal),
+        };
+        if !bounces.allows(kind, &scene.max_depth) {
+            break;
+        }
+
+        let sample_lights =
+            !rec.material.is_specular() && !rec.material.is_emissive() && !lights.is_empty();
+        if sample_lights {
+            radiance += throughput * direct_light(&ray, &rec, normal, scene, lights);
+        }
+
+        let (attenuation, scattered) = match rec.material.scatter(&ray, &rec) {
+            Some(scatter) => scatter,
+            None => break,
+        };
+        scatter_pdf = if sample_lights {
+            Some(rec.material.pdf(&ray, &rec, scattered.direction))
+        } else {
+            None
+        };
+        scatter_normal = normal;
+        throughput *= attenuation;
+        bounces.add(kind);
+
+        // Randomly end dim paths, boosting the ones that survive to make up
+        // for the light the others would have carried
+        if bounces.total >= scene.roulette_depth {
+            let survival = throughput
+                .x
+                .max(throughput.y)
+                .max(throughput.z)
+                .min(MAX_SURVIVAL);
+            if sample_1d() >= survival {
+                break;
+            }
+            throughput /= survival;
+        }
+
+        ray = scattered;
+    }
+
+    radiance
+}
+
+/// Light from a randomly picked light scattered at rec back along ray, zero
+/// when the light is blocked. Weighted against scatter picking the same
+/// direction. normal is the surface's, used to pick lights in front of it.
+fn direct_light(
+    ray: &Ray,
+    rec: &HitRecord,
+    normal: Option<Vec3>,
+    scene: &Scene,
+    lights: &LightList,
+) -> Vec3 {
+    let pick = sample_1d();
+    let u = sample_2d();
+    let light = match lights.sample(rec.p, normal, pick, u) {
+        Some(light) => light,
+        None => return Vec3::new(0.0, 0.0, 0.0),
+    };
+
+    let f = rec.material.eval(ray, rec, light.direction);
+    if f.x <= 0.0 && f.y <= 0.0 && f.z <= 0.0 {
+        return Vec3::new(0.0, 0.0, 0.0);
+    }
+
+    let shadow_ray = Ray {
+        origin: rec.p,
+        direction: light.direction,
+    };
+    let t_max = light.distance * (1.0 - SHADOW_SHORTENING);
+    if scene.world.hit(&shadow_ray, 0.001, t_max).is_some() {
+        return Vec3::new(0.0, 0.0, 0.0);
+    }
+
+    let weight = if light.delta {
+        1.0
+    } else {
+        power_heuristic(light.pdf, rec.material.pdf(ray, rec, light.direction))
+    };
+    f * light.radiance * (weight / light.pdf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn power_heuristic_weights_sum_to_one() {
+        let (a, b) = (0.3, 2.0);
+        assert!((power_heuristic(a, b) + power_heuristic(b, a) - 1.0).abs() < 1e-6);
+        assert_eq!(power_heuristic(1.0, 0.0), 1.0);
+    }
+
+    #[test]
+    fn bounces_respect_total_and_kind_limits() {
+        let max_depth = MaxDepth {
+            total: 3,
+            specular: 1,
+            ..MaxDepth::default()
+        };
+        let mut bounces = Bounces::default();
+        bounces.add(BounceKind::Specular);
+        assert!(!bounces.allows(BounceKind::Specular, &max_depth));
+        assert!(bounces.allows(BounceKind::Diffuse, &max_depth));
+
+        bounces.add(BounceKind::Diffuse);
+        bounces.add(BounceKind::Volume);
+        assert!(!bounces.allows(BounceKind::Diffuse, &max_depth));
+    }
+}
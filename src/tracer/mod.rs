@@ -1,17 +1,49 @@
+mod adaptive;
+mod aov;
 mod bounding_box;
 mod bvh;
 mod camera;
+mod checkpoint;
+mod color;
+mod denoise;
+mod environment;
+mod error;
+mod film;
+mod filter;
+mod framebuffer;
 mod hitable;
+mod integrator;
 mod ray;
 mod render;
+mod rng;
 mod scene;
+mod sky;
+mod tile;
+mod tone_map;
+mod transform;
 mod vec3;
 
+pub use adaptive::*;
+pub use aov::*;
 pub use bounding_box::*;
 pub use bvh::*;
 pub use camera::*;
+pub use checkpoint::*;
+pub use color::*;
+pub use denoise::*;
+pub use environment::*;
+pub use error::*;
+pub use film::*;
+pub use filter::*;
+pub use framebuffer::*;
 pub use hitable::*;
+pub use integrator::*;
 pub use ray::*;
 pub use render::*;
+pub use rng::*;
 pub use scene::*;
+pub use sky::*;
+pub use tile::*;
+pub use tone_map::*;
+pub use transform::*;
 pub use vec3::*;
@@ -1,77 +1,112 @@
-use super::hitable::Hitable;
-use super::ray::Ray;
+use super::checkpoint::{Checkpoint, PixelSamples};
+use super::error::RenderError;
+use super::film::FilmTile;
+use super::framebuffer::{aov_path, extension, suffixed_path, Features, Framebuffer};
+use super::integrator::radiance;
 use super::scene::{AnimatedScene, Scene};
-use super::vec3::Vec3;
-use rand::prelude::*;
-use std::f32::MAX;
+use super::tile::{gen_tiles, Tile};
+use crate::light::LightList;
+use crate::sampler::{sample_2d, start_sample, use_sampler};
 
-use image;
 use indicatif::{ProgressBar, ProgressStyle};
 use num_cpus;
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    mpsc, Arc,
+};
 use std::thread;
+use std::time::Instant;
 
-fn color(r: &Ray, world: &impl Hitable, depth: i32) -> Vec3 {
-    let rec = match world.hit(r, 0.001, MAX) {
-        Some(rec) => rec,
-        None => return Vec3::new(0.0, 0.0, 0.0),
-    };
-
-    let emitted = rec.material.emitted(rec.u, rec.v, rec.p);
-    if depth >= 50 {
-        return emitted;
-    }
-
-    let (attenuation, scattered) = match rec.material.scatter(r, &rec) {
-        Some((attenuation, scattered)) => (attenuation, scattered),
-        None => return emitted,
-    };
-
-    emitted + attenuation * color(&scattered, world, depth + 1)
-}
-
-fn sample_color(scene: &Scene, i: i32, j: i32) -> Vec3 {
-    let Scene {
-        ns,
-        nx,
-        cam,
-        world,
-        ny,
-    } = scene;
-    let mut rng = thread_rng();
-    let nsf = *ns as f32;
+/// Take the samples numbered first_sample..first_sample + samples in pixel
+/// (i, j), lit by scene's lights, collecting features when asked. Samples
+/// are splatted into film and their sums returned.
+fn sample_pixel(
+    scene: &Scene,
+    lights: &LightList,
+    film: &mut FilmTile,
+    (i, j): (i32, i32),
+    (first_sample, samples): (u32, u32),
+    collect_features: bool,
+) -> PixelSamples {
+    let Scene { nx, cam, ny, .. } = scene;
     let nxf = *nx as f32;
     let nyf = *ny as f32;
+    // Pixel index counted from the top, like the checkpoint and framebuffer
+    let pixel_index = ((ny - 1 - j) * nx + i) as u64;
 
-    let mut col = Vec3::new(0.0, 0.0, 0.0);
-    for _s in 0..*ns {
-        let u = (i as f32 + rng.gen::<f32>()) / nxf;
-        let v = (j as f32 + rng.gen::<f32>()) / nyf;
+    let mut pixel = PixelSamples::default();
+    for s in first_sample..first_sample + samples {
+        start_sample(scene.seed, pixel_index, s);
+        let (du, dv) = sample_2d();
+        let u = (i as f32 + du) / nxf;
+        let v = (j as f32 + dv) / nyf;
         let r = cam.get_ray(u, v);
-        col += color(&r, world, 0);
+        let mut features = Features::default();
+        let features_ref = if collect_features {
+            Some(&mut features)
+        } else {
+            None
+        };
+        let col = radiance(&r, scene, lights, features_ref);
+        film.splat(i as f32 + du, (ny - j) as f32 - dv, col);
+        pixel.add(&PixelSamples::sample(col, features));
     }
-
-    col /= nsf;
-    col.sqrt()
+    pixel
 }
 
-fn render_section(scene: Scene, starty: i32, endy: i32, pb: ProgressBar) -> Vec<u8> {
-    let mut file: Vec<u8> = Vec::with_capacity((endy - starty) as usize * scene.nx as usize * 3);
-    let Scene { nx, .. } = scene;
+/// Take this pass's samples for every pixel in the tile. plan holds the first
+/// sample number and sample count of every pixel in the image.
+fn render_tile(
+    scene: &Scene,
+    lights: &LightList,
+    tile: &Tile,
+    plan: &[(u32, u32)],
+    collect_features: bool,
+) -> FilmTile {
+    let mut film = FilmTile::new(tile, scene.nx, scene.ny, scene.filter);
 
-    for j in (starty..endy).rev() {
-        for i in 0..nx {
-            let col = sample_color(&scene, i, j);
-            file.push((255.99 * col.x).max(0.0).min(255.0) as u8);
-            file.push((255.99 * col.y).max(0.0).min(255.0) as u8);
-            file.push((255.99 * col.z).max(0.0).min(255.0) as u8);
+    for y in tile.y0..tile.y1 {
+        let j = scene.ny - 1 - y;
+        for i in tile.x0..tile.x1 {
+            let pixel = sample_pixel(
+                scene,
+                lights,
+                &mut film,
+                (i, j),
+                plan[(y * scene.nx + i) as usize],
+                collect_features,
+            );
+            film.add_samples(i, y, &pixel);
         }
-        pb.inc(1);
     }
-    file
+    film
+}
+
+/// How many more samples each pixel of the checkpoint needs, 0 once it is done
+fn samples_needed(scene: &Scene, checkpoint: &Checkpoint) -> Vec<u32> {
+    match &scene.adaptive {
+        Some(adaptive) => adaptive
+            .done_pixels(checkpoint.nx, checkpoint.ny, &checkpoint.pixels)
+            .iter()
+            .zip(&checkpoint.pixels)
+            .map(|(done, pixel)| {
+                if *done {
+                    0
+                } else {
+                    adaptive.max_samples - pixel.count
+                }
+            })
+            .collect(),
+        None => checkpoint
+            .pixels
+            .iter()
+            .map(|pixel| (scene.ns as u32).saturating_sub(pixel.count))
+            .collect(),
+    }
 }
 
-fn render_progress_bar(ny: i32) -> ProgressBar {
-    let pb = ProgressBar::new(ny as u64);
+fn render_progress_bar(tile_count: usize) -> ProgressBar {
+    let pb = ProgressBar::new(tile_count as u64);
     pb.set_style(
         ProgressStyle::default_bar()
             .template("[{elapsed_precise}] {msg} [{bar:50.cyan/blue}] ({eta})")
@@ -82,57 +117,273 @@ fn render_progress_bar(ny: i32) -> ProgressBar {
     pb
 }
 
-pub fn render(scene: Scene, path: String) {
-    let Scene { nx, ny, .. } = scene;
-    let mut file: Vec<u8> = Vec::with_capacity((nx as usize) * (ny as usize) * 3);
+fn validate_scene(scene: &Scene) -> Result<(), RenderError> {
+    if scene.nx <= 0 || scene.ny <= 0 {
+        return Err(RenderError::InvalidScene(format!(
+            "image size must be positive, got {}x{}",
+            scene.nx, scene.ny
+        )));
+    }
 
-    let thread_count = num_cpus::get();
-    let mut render_threads: Vec<thread::JoinHandle<Vec<u8>>> = Vec::with_capacity(thread_count);
-    let y_section_size = ny / thread_count as i32;
-    let mut start_y = ny - y_section_size;
-    let mut end_y = ny;
-    let pb = render_progress_bar(ny);
+    if scene.ns <= 0 {
+        return Err(RenderError::InvalidScene(format!(
+            "samples per pixel must be positive, got {}",
+            scene.ns
+        )));
+    }
+
+    if scene.samples_per_pass <= 0 {
+        return Err(RenderError::InvalidScene(format!(
+            "samples per pass must be positive, got {}",
+            scene.samples_per_pass
+        )));
+    }
+
+    let radius = scene.filter.radius();
+    if !(radius > 0.0 && radius.is_finite()) {
+        return Err(RenderError::InvalidScene(format!(
+            "filter radius must be positive, got {}",
+            radius
+        )));
+    }
+
+    if let Some(adaptive) = &scene.adaptive {
+        if adaptive.max_samples == 0 || adaptive.min_samples > adaptive.max_samples {
+            return Err(RenderError::InvalidScene(format!(
+                "adaptive sampling needs 0 < min samples <= max samples, got {} and {}",
+                adaptive.min_samples, adaptive.max_samples
+            )));
+        }
+    }
+
+    if scene.tile_size <= 0 {
+        return Err(RenderError::InvalidScene(format!(
+            "tile size must be positive, got {}",
+            scene.tile_size
+        )));
+    }
+
+    Ok(())
+}
+
+/// Render the scene into a framebuffer of linear color values
+pub fn render_to_framebuffer(scene: &Scene) -> Result<Framebuffer, RenderError> {
+    validate_scene(scene)?;
+    let checkpoint = Checkpoint::new(scene.nx as u32, scene.ny as u32);
+    render_progressive(scene, checkpoint)
+}
+
+/// Continue a render from the checkpoint file at checkpoint_path until every
+/// pixel has all the samples the scene asks for
+pub fn resume_to_framebuffer(
+    scene: &Scene,
+    checkpoint_path: &str,
+) -> Result<Framebuffer, RenderError> {
+    validate_scene(scene)?;
+    let checkpoint = Checkpoint::load(checkpoint_path)?;
+    if checkpoint.nx != scene.nx as u32 || checkpoint.ny != scene.ny as u32 {
+        return Err(RenderError::Checkpoint(format!(
+            "checkpoint is {}x{} but the scene is {}x{}",
+            checkpoint.nx, checkpoint.ny, scene.nx, scene.ny
+        )));
+    }
+    render_progressive(scene, checkpoint)
+}
+
+/// Add passes of samples over the whole image to checkpoint until no pixel
+/// needs more, saving checkpoints along the way
+fn render_progressive(
+    scene: &Scene,
+    mut checkpoint: Checkpoint,
+) -> Result<Framebuffer, RenderError> {
+    let collect_features = scene.denoiser.is_some() || !scene.aovs.is_empty();
+    let samples_per_pass = scene.samples_per_pass as u32;
+    let tiles = Arc::new(gen_tiles(
+        scene.nx,
+        scene.ny,
+        scene.tile_size,
+        scene.tile_order,
+    ));
+
+    // With adaptive sampling this is an upper bound, most renders finish early
+    let remaining_samples = samples_needed(scene, &checkpoint)
+        .into_iter()
+        .max()
+        .unwrap_or(0);
+    let passes = remaining_samples.div_ceil(samples_per_pass);
+    let pb = render_progress_bar(tiles.len() * passes as usize);
+    let lights = Arc::new(LightList::from_scene(scene));
+
+    let mut last_checkpoint = Instant::now();
+    loop {
+        let plan: Vec<(u32, u32)> = samples_needed(scene, &checkpoint)
+            .iter()
+            .zip(&checkpoint.pixels)
+            .map(|(needed, pixel)| (pixel.count, (*needed).min(samples_per_pass)))
+            .collect();
+        if plan.iter().all(|(_, samples)| *samples == 0) {
+            break;
+        }
+
+        if let Err(e) = render_pass(
+            scene,
+            &lights,
+            &tiles,
+            Arc::new(plan),
+            &mut checkpoint,
+            collect_features,
+            &pb,
+        ) {
+            pb.abandon_with_message("Failed");
+            return Err(e);
+        }
+
+        if let Some(opts) = &scene.checkpoint {
+            if last_checkpoint.elapsed() >= opts.interval {
+                checkpoint.save(&opts.path)?;
+                last_checkpoint = Instant::now();
+            }
+        }
+    }
 
+    // Keep the finished state too, so the render can be resumed with a higher ns
+    if let Some(opts) = &scene.checkpoint {
+        checkpoint.save(&opts.path)?;
+    }
+
+    pb.finish_with_message("Complete");
+
+    let mut framebuffer = checkpoint.to_framebuffer(collect_features);
+    if let (Some(denoiser), Some(features)) = (&scene.denoiser, &framebuffer.features) {
+        framebuffer.pixels = denoiser.denoise(
+            framebuffer.nx,
+            framebuffer.ny,
+            &framebuffer.pixels,
+            features,
+        );
+    }
+
+    Ok(framebuffer)
+}
+
+/// Take one pass of samples over every tile, adding them to checkpoint
+fn render_pass(
+    scene: &Scene,
+    lights: &Arc<LightList>,
+    tiles: &Arc<Vec<Tile>>,
+    plan: Arc<Vec<(u32, u32)>>,
+    checkpoint: &mut Checkpoint,
+    collect_features: bool,
+    pb: &ProgressBar,
+) -> Result<(), RenderError> {
+    let nx = scene.nx;
+    let next_tile = Arc::new(AtomicUsize::new(0));
+    let (sender, receiver) = mpsc::channel();
+
+    // Each thread pulls the next tile off the shared queue until it is empty,
+    // so threads that land on cheap tiles simply end up rendering more of them.
+    let thread_count = num_cpus::get();
+    let mut render_threads: Vec<thread::JoinHandle<()>> = Vec::with_capacity(thread_count);
     for _thread in 0..thread_count {
         let thread_scene = scene.clone();
+        let thread_lights = lights.clone();
+        let thread_tiles = tiles.clone();
+        let thread_plan = plan.clone();
+        let thread_next_tile = next_tile.clone();
+        let thread_sender = sender.clone();
         let thread_pb = pb.clone();
-        let render_thread =
-            thread::spawn(move || render_section(thread_scene, start_y, end_y, thread_pb));
+        let render_thread = thread::spawn(move || {
+            use_sampler(&thread_scene.sampler);
+            loop {
+                let index = thread_next_tile.fetch_add(1, Ordering::SeqCst);
+                let tile = match thread_tiles.get(index) {
+                    Some(tile) => *tile,
+                    None => break,
+                };
+                let film = render_tile(
+                    &thread_scene,
+                    &thread_lights,
+                    &tile,
+                    &thread_plan,
+                    collect_features,
+                );
+                if thread_sender.send((index, film)).is_err() {
+                    break;
+                }
+                thread_pb.inc(1);
+            }
+        });
         render_threads.push(render_thread);
-        end_y = start_y;
-        start_y -= y_section_size;
     }
+    drop(sender);
 
-    for render_thread in render_threads {
-        file.extend(render_thread.join().unwrap());
-    }
-
-    // Render remaining y columns
-    let remaining_y_columns = ny - (y_section_size * thread_count as i32);
-    if remaining_y_columns > 0 {
-        render_threads = Vec::with_capacity(remaining_y_columns as usize);
-        for column in (0..remaining_y_columns).rev() {
-            let thread_scene = scene.clone();
-            let thread_pb = pb.clone();
-            let render_thread =
-                thread::spawn(move || render_section(thread_scene, column - 1, column, thread_pb));
-            render_threads.push(render_thread);
+    // Films overlap when the filter reaches past the tiles. They are merged in
+    // queue order whatever order they finish in, so the sums, and with them
+    // the image, don't depend on thread timing.
+    let mut finished: Vec<Option<FilmTile>> = (0..tiles.len()).map(|_| None).collect();
+    let mut next_merge = 0;
+    for (index, film) in receiver {
+        finished[index] = Some(film);
+        while let Some(film) = finished.get_mut(next_merge).and_then(Option::take) {
+            let mut pixels = film.pixels.iter();
+            for y in film.bounds.y0..film.bounds.y1 {
+                for x in film.bounds.x0..film.bounds.x1 {
+                    checkpoint.pixels[(y * nx + x) as usize].add(pixels.next().unwrap());
+                }
+            }
+            next_merge += 1;
         }
+    }
 
-        for render_thread in render_threads {
-            file.extend(render_thread.join().unwrap());
-        }
+    let mut panicked = false;
+    for render_thread in render_threads {
+        panicked |= render_thread.join().is_err();
     }
 
-    pb.finish_with_message("Complete");
+    if panicked {
+        return Err(RenderError::ThreadPanicked);
+    }
+    Ok(())
+}
+
+/// Render the scene and save it to path, exiting the process on failure
+pub fn render(scene: Scene, path: String) {
+    if let Err(e) =
+        render_to_framebuffer(&scene).and_then(|framebuffer| save(&scene, &framebuffer, &path))
+    {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+/// Resume the render checkpointed at checkpoint_path and save it to path,
+/// exiting the process on failure
+pub fn resume(scene: Scene, checkpoint_path: &str, path: String) {
+    if let Err(e) = resume_to_framebuffer(&scene, checkpoint_path)
+        .and_then(|framebuffer| save(&scene, &framebuffer, &path))
+    {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
 
-    match image::save_buffer(path, &file, nx as u32, ny as u32, image::ColorType::RGB(8)) {
-        Err(e) => {
-            eprintln!("Error: {}", e);
-            std::process::exit(1);
+/// Save the framebuffer and any AOVs the scene asks for
+fn save(scene: &Scene, framebuffer: &Framebuffer, path: &str) -> Result<(), RenderError> {
+    if let Some(adaptive) = &scene.adaptive {
+        if adaptive.sample_count_image {
+            framebuffer.save_sample_counts(&suffixed_path(path, "spp"), adaptive.max_samples)?;
         }
-        _ => {}
     }
+
+    if !scene.aovs.is_empty() && extension(path).as_deref() == Some("exr") {
+        return framebuffer.save_layered_exr(path, &scene.aovs);
+    }
+
+    framebuffer.save(path, &scene.tone_map)?;
+    for aov in &scene.aovs {
+        framebuffer.save_aov(*aov, &aov_path(path, *aov))?;
+    }
+    Ok(())
 }
 
 pub fn render_animation(scene: AnimatedScene, path: String) {
@@ -149,3 +400,67 @@ pub fn render_animation(scene: AnimatedScene, path: String) {
         frame += 1;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::{ConstantMedium, Sphere};
+    use crate::material;
+    use crate::tracer::{BVHNode, Camera, CameraOpts, TileOrder, Vec3};
+
+    fn test_scene() -> Scene {
+        let world = BVHNode::new(vec![
+            Box::new(Sphere {
+                center: Vec3::new(0.0, 0.0, -1.0),
+                radius: 0.5,
+                material: material::dielectric(1.5),
+            }),
+            Box::new(ConstantMedium {
+                boundry: Box::new(Sphere {
+                    center: Vec3::new(0.0, -100.5, -1.0),
+                    radius: 100.0,
+                    material: material::lambertion(0.5, 0.5, 0.5),
+                }),
+                density: 0.5,
+                phase_function: material::isotropic(0.8, 0.6, 0.2),
+            }),
+            Box::new(Sphere {
+                center: Vec3::new(0.0, 2.0, -1.0),
+                radius: 1.0,
+                material: material::diffuse_light(4.0, 4.0, 4.0),
+            }),
+        ]);
+        let cam = Camera::new(CameraOpts {
+            lookfrom: Vec3::new(0.0, 0.0, 1.0),
+            lookat: Vec3::new(0.0, 0.0, -1.0),
+            vup: Vec3::new(0.0, 1.0, 0.0),
+            vfow: 60.0,
+            aspect: 1.0,
+            aperture: 0.1,
+            focus_dist: 2.0,
+        });
+        Scene::new(12, 12, 6, cam, Arc::new(world))
+    }
+
+    #[test]
+    fn same_seed_renders_identical_images_with_any_tiling() {
+        let scene = test_scene();
+        let mut retiled = test_scene();
+        retiled.tile_size = 5;
+        retiled.tile_order = TileOrder::Scanline;
+
+        let a = render_to_framebuffer(&scene).unwrap();
+        let b = render_to_framebuffer(&retiled).unwrap();
+        for (a, b) in a.pixels.iter().zip(&b.pixels) {
+            assert_eq!(
+                (a.x.to_bits(), a.y.to_bits(), a.z.to_bits()),
+                (b.x.to_bits(), b.y.to_bits(), b.z.to_bits())
+            );
+        }
+
+        let mut reseeded = test_scene();
+        reseeded.seed = 1;
+        let c = render_to_framebuffer(&reseeded).unwrap();
+        assert!(a.pixels.iter().zip(&c.pixels).any(|(a, c)| a.x != c.x));
+    }
+}
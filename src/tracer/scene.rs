@@ -1,4 +1,10 @@
-use super::{camera::Camera, hitable::Hitable};
+use super::{
+    adaptive::AdaptiveSampling, aov::Aov, camera::Camera, checkpoint::CheckpointOpts,
+    denoise::Denoiser, environment::Environment, filter::Filter, hitable::Hitable,
+    integrator::MaxDepth, tile::TileOrder, tone_map::ToneMap,
+};
+use crate::light::BoxLight;
+use crate::sampler::{BoxSampler, RandomSampler};
 use std::sync::Arc;
 
 pub type World = Arc<dyn Hitable>;
@@ -10,6 +16,71 @@ pub struct Scene {
     pub ns: i32,
     pub cam: Camera,
     pub world: World,
+    /// What rays that miss the world see, black by default
+    pub environment: Environment,
+    /// Lights that aren't part of the world, like point and spot lights.
+    /// Emissive hitables in the world are found on their own.
+    pub lights: Vec<BoxLight>,
+    /// Seed of every random number used while rendering. The same seed always
+    /// gives the same image, no matter how many threads render it.
+    pub seed: u64,
+    /// Where the sample values of each pixel come from, i.e. a SobolSampler
+    /// for less noise at the same sample count
+    pub sampler: BoxSampler,
+    /// The image is rendered in passes over every pixel, each adding this
+    /// many samples
+    pub samples_per_pass: i32,
+    /// Take more samples in noisy pixels and fewer in clean ones. When set,
+    /// the sample counts come from here and ns is ignored.
+    pub adaptive: Option<AdaptiveSampling>,
+    /// Most bounces a path may take
+    pub max_depth: MaxDepth,
+    /// Paths start ending at random by Russian roulette after this many
+    /// bounces, dimmer paths being more likely to end
+    pub roulette_depth: u32,
+    /// How samples are weighted into the pixels around them
+    pub filter: Filter,
+    /// Periodically save the render's progress so it can be resumed
+    pub checkpoint: Option<CheckpointOpts>,
+    /// Width and height in pixels of the tiles handed out to render threads
+    pub tile_size: i32,
+    pub tile_order: TileOrder,
+    /// Display transform used when the render is saved as an 8 bit image
+    pub tone_map: ToneMap,
+    /// Denoise the render using features collected at the first hit of each
+    /// camera ray
+    pub denoiser: Option<Denoiser>,
+    /// Passes written next to the render. Each pass gets its own file, unless
+    /// the render is saved as .exr where they are added as layers.
+    pub aovs: Vec<Aov>,
+}
+
+impl Scene {
+    /// Create a scene with the default render settings
+    pub fn new(nx: i32, ny: i32, ns: i32, cam: Camera, world: World) -> Scene {
+        Scene {
+            nx,
+            ny,
+            ns,
+            cam,
+            world,
+            environment: Environment::default(),
+            lights: Vec::new(),
+            seed: 0,
+            sampler: Box::new(RandomSampler),
+            samples_per_pass: 16,
+            adaptive: None,
+            max_depth: MaxDepth::default(),
+            roulette_depth: 3,
+            filter: Filter::default(),
+            checkpoint: None,
+            tile_size: 32,
+            tile_order: TileOrder::default(),
+            tone_map: ToneMap::default(),
+            denoiser: None,
+            aovs: Vec::new(),
+        }
+    }
 }
 
 #[derive(Clone)]
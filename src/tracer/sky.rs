@@ -0,0 +1,256 @@
+use super::color::xyz_to_rgb;
+use super::vec3::Vec3;
+use crate::sampler::{concentric_disk, orthonormal_basis};
+use std::f32::consts::PI;
+
+/// Sky and sun luminance come out in kcd/m², scaled so a white floor under a
+/// high sun renders close to 1
+const SKY_SCALE: f32 = 0.03;
+
+/// Illuminance of the sun above the atmosphere in klux
+const SOLAR_ILLUMINANCE: f32 = 128.0;
+
+/// Chance of sampling the sun rather than the sky when lighting a point
+const SUN_SAMPLE_CHANCE: f32 = 0.5;
+
+/// Clear daylight sky from Preetham, Shirley and Smits' analytic model, with
+/// a sun disk attenuated by the same atmosphere. Nothing is seen below the
+/// horizon.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Sky {
+    /// Degrees of the sun above the horizon
+    pub sun_elevation: f32,
+    /// Degrees of the sun around the vertical axis, from -z towards +x
+    pub sun_azimuth: f32,
+    /// Haziness of the air, from 2 for a very clear day to 10 for a hazy one
+    pub turbidity: f32,
+    /// Angular diameter of the sun's disk in degrees. The sun lights the
+    /// scene just as brightly at any size, bigger suns give softer shadows.
+    pub sun_size: f32,
+    /// Scale applied to sky and sun
+    pub intensity: f32,
+}
+
+impl Default for Sky {
+    fn default() -> Sky {
+        Sky {
+            sun_elevation: 45.0,
+            sun_azimuth: 0.0,
+            turbidity: 3.0,
+            sun_size: 0.53,
+            intensity: 1.0,
+        }
+    }
+}
+
+impl Sky {
+    pub fn sun_direction(&self) -> Vec3 {
+        let elevation = self.sun_elevation.to_radians();
+        let azimuth = self.sun_azimuth.to_radians();
+        Vec3::new(
+            elevation.cos() * azimuth.sin(),
+            elevation.sin(),
+            -elevation.cos() * azimuth.cos(),
+        )
+    }
+
+    /// Light arriving along the reverse of direction, including the sun
+    pub fn radiance(&self, direction: Vec3) -> Vec3 {
+        let direction = direction.unit_vector();
+        let mut radiance = self.sky_radiance(direction);
+        if direction.dot(&self.sun_direction()) >= self.sun_cos_radius() {
+            radiance += self.sun_radiance();
+        }
+        radiance
+    }
+
+    /// Light scattered towards the viewer by the sky along the unit
+    /// direction, without the sun
+    fn sky_radiance(&self, direction: Vec3) -> Vec3 {
+        if direction.y <= 0.0 {
+            return Vec3::new(0.0, 0.0, 0.0);
+        }
+
+        let sun = self.sun_direction();
+        // Zenith angles of the view and the sun, and the angle between them
+        let theta = direction.y.min(1.0).acos();
+        let theta_sun = sun.y.clamp(0.0, 1.0).acos();
+        let gamma = direction.dot(&sun).clamp(-1.0, 1.0).acos();
+
+        let t = self.turbidity;
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_sun);
+        let zenith_luminance = ((4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192).max(0.0);
+        let zenith_chromaticity = |coefficients: [[f32; 4]; 3]| {
+            let angles = [theta_sun.powi(3), theta_sun.powi(2), theta_sun, 1.0];
+            let dot = |row: [f32; 4]| row.iter().zip(&angles).map(|(a, b)| a * b).sum::<f32>();
+            t * t * dot(coefficients[0]) + t * dot(coefficients[1]) + dot(coefficients[2])
+        };
+        let zenith_x = zenith_chromaticity([
+            [0.00166, -0.00375, 0.00209, 0.0],
+            [-0.02903, 0.06377, -0.03202, 0.00394],
+            [0.11693, -0.21196, 0.06052, 0.25886],
+        ]);
+        let zenith_y = zenith_chromaticity([
+            [0.00275, -0.00610, 0.00317, 0.0],
+            [-0.04214, 0.08970, -0.04153, 0.00516],
+            [0.15346, -0.26756, 0.06670, 0.26688],
+        ]);
+
+        // Perez et al.'s sky distribution, relative to the zenith
+        let relative = |[a, b, c, d, e]: [f32; 5]| {
+            let perez = |theta: f32, gamma: f32| {
+                (1.0 + a * (b / theta.cos().max(0.01)).exp())
+                    * (1.0 + c * (d * gamma).exp() + e * gamma.cos().powi(2))
+            };
+            perez(theta, gamma) / perez(0.0, theta_sun)
+        };
+        let luminance = zenith_luminance
+            * relative([
+                0.1787 * t - 1.4630,
+                -0.3554 * t + 0.4275,
+                -0.0227 * t + 5.3251,
+                0.1206 * t - 2.5771,
+                -0.0670 * t + 0.3703,
+            ]);
+        let x = zenith_x
+            * relative([
+                -0.0193 * t - 0.2592,
+                -0.0665 * t + 0.0008,
+                -0.0004 * t + 0.2125,
+                -0.0641 * t - 0.8989,
+                -0.0033 * t + 0.0452,
+            ]);
+        let y = zenith_y
+            * relative([
+                -0.0167 * t - 0.2608,
+                -0.0950 * t + 0.0092,
+                -0.0079 * t + 0.2102,
+                -0.0441 * t - 1.6537,
+                -0.0109 * t + 0.0529,
+            ]);
+
+        self.intensity * SKY_SCALE * xyy_to_rgb(x, y, luminance)
+    }
+
+    /// Light from every point of the sun's disk
+    fn sun_radiance(&self) -> Vec3 {
+        if self.sun_elevation <= 0.0 {
+            return Vec3::new(0.0, 0.0, 0.0);
+        }
+
+        // Relative optical mass of the air the sunlight passes through
+        let theta_sun = (90.0 - self.sun_elevation).to_radians();
+        let mass =
+            1.0 / (theta_sun.cos() + 0.15 * (93.885 - (90.0 - self.sun_elevation)).powf(-1.253));
+        let beta = 0.04608 * self.turbidity - 0.04586;
+        // Rayleigh and aerosol scattering at red, green and blue wavelengths
+        // in micrometers
+        let transmittance = |wavelength: f32| {
+            let rayleigh = -0.008735 * wavelength.powf(-4.08) * mass;
+            let aerosol = -beta * wavelength.powf(-1.3) * mass;
+            (rayleigh + aerosol).exp()
+        };
+
+        let solid_angle = 2.0 * PI * (1.0 - self.sun_cos_radius());
+        self.intensity * SKY_SCALE * SOLAR_ILLUMINANCE / solid_angle
+            * Vec3::new(
+                transmittance(0.65),
+                transmittance(0.57),
+                transmittance(0.475),
+            )
+    }
+
+    fn sun_cos_radius(&self) -> f32 {
+        (0.5 * self.sun_size).to_radians().cos()
+    }
+
+    /// Pick a unit direction towards either the sun or the sky, returned with
+    /// its solid angle density
+    pub fn sample(&self, u: (f32, f32)) -> Option<(Vec3, f32)> {
+        let direction = if u.0 < SUN_SAMPLE_CHANCE {
+            // Uniformly over the cone the sun fills
+            let u = (u.0 / SUN_SAMPLE_CHANCE, u.1);
+            let cos_theta = 1.0 + u.0 * (self.sun_cos_radius() - 1.0);
+            let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+            let phi = 2.0 * PI * u.1;
+            let w = self.sun_direction();
+            let (a, b) = orthonormal_basis(w);
+            sin_theta * phi.cos() * a + sin_theta * phi.sin() * b + cos_theta * w
+        } else {
+            // Cosine weighted over the upper hemisphere
+            let u = ((u.0 - SUN_SAMPLE_CHANCE) / (1.0 - SUN_SAMPLE_CHANCE), u.1);
+            let disk = concentric_disk(u);
+            let y = (1.0 - disk.x * disk.x - disk.y * disk.y).max(0.0).sqrt();
+            Vec3::new(disk.x, y, disk.y)
+        };
+
+        let pdf = self.pdf(direction);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some((direction, pdf))
+    }
+
+    /// Solid angle density with which sample picks direction
+    pub fn pdf(&self, direction: Vec3) -> f32 {
+        let direction = direction.unit_vector();
+        let cos_radius = self.sun_cos_radius();
+        let sun_pdf = if direction.dot(&self.sun_direction()) >= cos_radius {
+            1.0 / (2.0 * PI * (1.0 - cos_radius))
+        } else {
+            0.0
+        };
+        let sky_pdf = direction.y.max(0.0) / PI;
+        SUN_SAMPLE_CHANCE * sun_pdf + (1.0 - SUN_SAMPLE_CHANCE) * sky_pdf
+    }
+}
+
+/// Linear sRGB color of a CIE xyY color
+fn xyy_to_rgb(x: f32, y: f32, luminance: f32) -> Vec3 {
+    if y <= 0.0 {
+        return Vec3::new(0.0, 0.0, 0.0);
+    }
+    xyz_to_rgb(Vec3::new(
+        x / y * luminance,
+        luminance,
+        (1.0 - x - y) / y * luminance,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampler::uniform_sphere;
+
+    #[test]
+    fn sky_is_blue_and_the_sun_outshines_it() {
+        let sky = Sky::default();
+        let zenith = sky.radiance(Vec3::new(0.0, 1.0, 0.0));
+        assert!(zenith.z > zenith.x);
+        assert!(sky.radiance(sky.sun_direction()).x > 1000.0 * zenith.x);
+        assert_eq!(sky.radiance(Vec3::new(0.0, -1.0, 0.0)).y, 0.0);
+    }
+
+    #[test]
+    fn samples_match_the_pdf_which_integrates_to_one() {
+        let sky = Sky {
+            sun_size: 20.0,
+            ..Sky::default()
+        };
+        for u in &[(0.2, 0.7), (0.8, 0.1)] {
+            let (direction, pdf) = sky.sample(*u).unwrap();
+            assert!((pdf - sky.pdf(direction)).abs() < 1e-3 * pdf);
+        }
+
+        let n = 400;
+        let mut integral = 0.0;
+        for i in 0..n {
+            for j in 0..n {
+                let u = ((i as f32 + 0.5) / n as f32, (j as f32 + 0.5) / n as f32);
+                integral += sky.pdf(uniform_sphere(u));
+            }
+        }
+        integral *= 4.0 * PI / (n * n) as f32;
+        assert!((integral - 1.0).abs() < 0.02, "{}", integral);
+    }
+}
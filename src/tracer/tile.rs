@@ -0,0 +1,180 @@
+/// A rectangular region of the image in pixel coordinates, with the origin at
+/// the top left of the image. `x1` and `y1` are exclusive.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Tile {
+    pub x0: i32,
+    pub y0: i32,
+    pub x1: i32,
+    pub y1: i32,
+}
+
+impl Tile {
+    pub fn width(&self) -> i32 {
+        self.x1 - self.x0
+    }
+
+    pub fn height(&self) -> i32 {
+        self.y1 - self.y0
+    }
+}
+
+/// The order render threads pull tiles from the queue in
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum TileOrder {
+    /// Left to right, top to bottom
+    Scanline,
+    /// Outwards from the center of the image
+    #[default]
+    Spiral,
+    /// Along a hilbert curve, keeping consecutive tiles spatially close
+    Hilbert,
+}
+
+/// Split an nx by ny image into tiles of at most tile_size by tile_size pixels
+pub fn gen_tiles(nx: i32, ny: i32, tile_size: i32, order: TileOrder) -> Vec<Tile> {
+    let tile_size = tile_size.max(1);
+    let cols = (nx + tile_size - 1) / tile_size;
+    let rows = (ny + tile_size - 1) / tile_size;
+
+    let cells = match order {
+        TileOrder::Scanline => scanline_cells(cols, rows),
+        TileOrder::Spiral => spiral_cells(cols, rows),
+        TileOrder::Hilbert => hilbert_cells(cols, rows),
+    };
+
+    cells
+        .into_iter()
+        .map(|(cx, cy)| Tile {
+            x0: cx * tile_size,
+            y0: cy * tile_size,
+            x1: ((cx + 1) * tile_size).min(nx),
+            y1: ((cy + 1) * tile_size).min(ny),
+        })
+        .collect()
+}
+
+fn scanline_cells(cols: i32, rows: i32) -> Vec<(i32, i32)> {
+    let mut cells = Vec::with_capacity((cols * rows) as usize);
+    for cy in 0..rows {
+        for cx in 0..cols {
+            cells.push((cx, cy));
+        }
+    }
+    cells
+}
+
+fn spiral_cells(cols: i32, rows: i32) -> Vec<(i32, i32)> {
+    let total = (cols * rows) as usize;
+    let mut cells = Vec::with_capacity(total);
+    let (mut x, mut y) = ((cols - 1) / 2, (rows - 1) / 2);
+    let directions = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+    let mut direction = 0;
+    let mut leg_length = 1;
+
+    while cells.len() < total {
+        // Each leg length is walked twice before growing: right, down, left 2, up 2...
+        for _ in 0..2 {
+            let (dx, dy) = directions[direction];
+            for _ in 0..leg_length {
+                if x >= 0 && x < cols && y >= 0 && y < rows {
+                    cells.push((x, y));
+                }
+                x += dx;
+                y += dy;
+            }
+            direction = (direction + 1) % 4;
+        }
+        leg_length += 1;
+    }
+
+    cells.truncate(total);
+    cells
+}
+
+fn hilbert_cells(cols: i32, rows: i32) -> Vec<(i32, i32)> {
+    let mut n = 1;
+    while n < cols.max(rows) {
+        n *= 2;
+    }
+
+    let mut cells = Vec::with_capacity((cols * rows) as usize);
+    for d in 0..n * n {
+        let (x, y) = hilbert_d2xy(n, d);
+        if x < cols && y < rows {
+            cells.push((x, y));
+        }
+    }
+    cells
+}
+
+/// Convert a distance along a hilbert curve filling an n by n grid to (x, y)
+fn hilbert_d2xy(n: i32, d: i32) -> (i32, i32) {
+    let (mut x, mut y) = (0, 0);
+    let mut t = d;
+    let mut s = 1;
+    while s < n {
+        let rx = 1 & (t / 2);
+        let ry = 1 & (t ^ rx);
+        if ry == 0 {
+            if rx == 1 {
+                x = s - 1 - x;
+                y = s - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        x += s * rx;
+        y += s * ry;
+        t /= 4;
+        s *= 2;
+    }
+    (x, y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_covers_image(nx: i32, ny: i32, tiles: &[Tile]) {
+        let mut covered = vec![0; (nx * ny) as usize];
+        for tile in tiles {
+            for y in tile.y0..tile.y1 {
+                for x in tile.x0..tile.x1 {
+                    covered[(y * nx + x) as usize] += 1;
+                }
+            }
+        }
+        assert!(covered.iter().all(|&count| count == 1));
+    }
+
+    #[test]
+    fn tiles_cover_every_pixel_once() {
+        for &order in &[TileOrder::Scanline, TileOrder::Spiral, TileOrder::Hilbert] {
+            assert_covers_image(100, 37, &gen_tiles(100, 37, 16, order));
+            assert_covers_image(7, 300, &gen_tiles(7, 300, 32, order));
+        }
+    }
+
+    #[test]
+    fn spiral_starts_at_center() {
+        let tiles = gen_tiles(48, 48, 16, TileOrder::Spiral);
+        assert_eq!(
+            tiles[0],
+            Tile {
+                x0: 16,
+                y0: 16,
+                x1: 32,
+                y1: 32
+            }
+        );
+    }
+
+    #[test]
+    fn hilbert_steps_to_neighbors() {
+        let tiles = gen_tiles(64, 64, 8, TileOrder::Hilbert);
+        for pair in tiles.windows(2) {
+            let dx = (pair[0].x0 - pair[1].x0).abs();
+            let dy = (pair[0].y0 - pair[1].y0).abs();
+            assert_eq!(dx + dy, 8);
+        }
+    }
+}
@@ -0,0 +1,118 @@
+use super::vec3::Vec3;
+
+/// Compresses scene referred radiance into display values in 0..1
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum ToneMapOperator {
+    /// Clip anything brighter than 1
+    #[default]
+    Clamp,
+    /// x / (1 + x), never quite reaches white
+    Reinhard,
+    /// Narkowicz's fit of the ACES filmic curve, with a soft shoulder and toe
+    Aces,
+}
+
+/// Transfer function used to encode display linear values for the output image
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum Oetf {
+    /// Square root, what rustracer has always written
+    #[default]
+    Gamma2,
+    /// The piecewise sRGB curve
+    Srgb,
+    /// Write values as is
+    Linear,
+}
+
+/// Display transform applied to the linear framebuffer when writing 8 bit images
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct ToneMap {
+    /// Exposure adjustment in stops, applied before the operator
+    pub exposure: f32,
+    pub operator: ToneMapOperator,
+    pub oetf: Oetf,
+}
+
+impl ToneMap {
+    /// Map a linear color to an encoded display color in 0..1
+    pub fn apply(&self, col: Vec3) -> Vec3 {
+        let exposed = col * 2f32.powf(self.exposure);
+        Vec3::new(
+            self.map_channel(exposed.x),
+            self.map_channel(exposed.y),
+            self.map_channel(exposed.z),
+        )
+    }
+
+    fn map_channel(&self, x: f32) -> f32 {
+        let x = x.max(0.0);
+        let mapped = match self.operator {
+            ToneMapOperator::Clamp => x,
+            ToneMapOperator::Reinhard => x / (1.0 + x),
+            ToneMapOperator::Aces => (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14),
+        }
+        .min(1.0);
+
+        match self.oetf {
+            Oetf::Gamma2 => mapped.sqrt(),
+            Oetf::Srgb => srgb_oetf(mapped),
+            Oetf::Linear => mapped,
+        }
+    }
+}
+
+pub fn srgb_oetf(x: f32) -> f32 {
+    if x <= 0.003_130_8 {
+        12.92 * x
+    } else {
+        1.055 * x.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::FloatCmp;
+
+    #[test]
+    fn default_matches_gamma_2() {
+        let col = ToneMap::default().apply(Vec3::new(0.25, 4.0, -1.0));
+        col.x.assert_nearly_eq(0.5);
+        col.y.assert_nearly_eq(1.0);
+        col.z.assert_nearly_eq(0.0);
+    }
+
+    #[test]
+    fn exposure_is_in_stops() {
+        let tone_map = ToneMap {
+            exposure: -2.0,
+            oetf: Oetf::Linear,
+            ..ToneMap::default()
+        };
+        tone_map
+            .apply(Vec3::new(2.0, 2.0, 2.0))
+            .x
+            .assert_nearly_eq(0.5);
+    }
+
+    #[test]
+    fn reinhard_and_aces_keep_bright_values_below_white() {
+        for &operator in &[ToneMapOperator::Reinhard, ToneMapOperator::Aces] {
+            let tone_map = ToneMap {
+                operator,
+                oetf: Oetf::Linear,
+                ..ToneMap::default()
+            };
+            let dim = tone_map.apply(Vec3::new(4.0, 4.0, 4.0)).x;
+            let bright = tone_map.apply(Vec3::new(10.0, 10.0, 10.0)).x;
+            assert!(dim < bright && bright <= 1.0);
+        }
+    }
+
+    #[test]
+    fn srgb_oetf_endpoints() {
+        srgb_oetf(0.0).assert_nearly_eq(0.0);
+        srgb_oetf(1.0).assert_nearly_eq(1.0);
+        srgb_oetf(0.001).assert_nearly_eq(0.01292);
+    }
+}
@@ -0,0 +1,376 @@
+use super::bounding_box::BoundingBox;
+use super::vec3::Vec3;
+use std::ops::Mul;
+
+/// Row major 4x4 matrix of an affine transform. `a * b` transforms by b and
+/// then by a.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Mat4 {
+    pub m: [[f32; 4]; 4],
+}
+
+impl Mat4 {
+    pub fn identity() -> Mat4 {
+        Mat4::scale(1.0, 1.0, 1.0)
+    }
+
+    pub fn translation(offset: Vec3) -> Mat4 {
+        let mut matrix = Mat4::identity();
+        matrix.m[0][3] = offset.x;
+        matrix.m[1][3] = offset.y;
+        matrix.m[2][3] = offset.z;
+        matrix
+    }
+
+    pub fn scale(x: f32, y: f32, z: f32) -> Mat4 {
+        Mat4 {
+            m: [
+                [x, 0.0, 0.0, 0.0],
+                [0.0, y, 0.0, 0.0],
+                [0.0, 0.0, z, 0.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        }
+    }
+
+    /// Rotation about the x axis by angle in degrees
+    pub fn rotation_x(angle: f32) -> Mat4 {
+        Mat4::rotation(Vec3::new(1.0, 0.0, 0.0), angle)
+    }
+
+    /// Rotation about the y axis by angle in degrees
+    pub fn rotation_y(angle: f32) -> Mat4 {
+        Mat4::rotation(Vec3::new(0.0, 1.0, 0.0), angle)
+    }
+
+    /// Rotation about the z axis by angle in degrees
+    pub fn rotation_z(angle: f32) -> Mat4 {
+        Mat4::rotation(Vec3::new(0.0, 0.0, 1.0), angle)
+    }
+
+    /// Counter clockwise rotation about axis by angle in degrees, looking
+    /// down the axis towards the origin
+    pub fn rotation(axis: Vec3, angle: f32) -> Mat4 {
+        let a = axis.unit_vector();
+        let (sin, cos) = angle.to_radians().sin_cos();
+        let t = 1.0 - cos;
+        Mat4 {
+            m: [
+                [
+                    t * a.x * a.x + cos,
+                    t * a.x * a.y - sin * a.z,
+                    t * a.x * a.z + sin * a.y,
+                    0.0,
+                ],
+                [
+                    t * a.x * a.y + sin * a.z,
+                    t * a.y * a.y + cos,
+                    t * a.y * a.z - sin * a.x,
+                    0.0,
+                ],
+                [
+                    t * a.x * a.z - sin * a.y,
+                    t * a.y * a.z + sin * a.x,
+                    t * a.z * a.z + cos,
+                    0.0,
+                ],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        }
+    }
+
+    /// Rotation by the unit quaternion x, y, z, w
+    pub fn rotation_quaternion(x: f32, y: f32, z: f32, w: f32) -> Mat4 {
+        Mat4 {
+            m: [
+                [
+                    1.0 - 2.0 * (y * y + z * z),
+                    2.0 * (x * y - z * w),
+                    2.0 * (x * z + y * w),
+                    0.0,
+                ],
+                [
+                    2.0 * (x * y + z * w),
+                    1.0 - 2.0 * (x * x + z * z),
+                    2.0 * (y * z - x * w),
+                    0.0,
+                ],
+                [
+                    2.0 * (x * z - y * w),
+                    2.0 * (y * z + x * w),
+                    1.0 - 2.0 * (x * x + y * y),
+                    0.0,
+                ],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        }
+    }
+
+    pub fn transpose(&self) -> Mat4 {
+        let mut m = [[0.0; 4]; 4];
+        for (row, values) in m.iter_mut().enumerate() {
+            for (col, value) in values.iter_mut().enumerate() {
+                *value = self.m[col][row];
+            }
+        }
+        Mat4 { m }
+    }
+
+    /// Determinant of the upper 3x3 part, negative for transforms that
+    /// mirror
+    pub fn determinant(&self) -> f32 {
+        let m = &self.m;
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+
+    /// The matrix undoing this one, None if it flattens space or has entries
+    /// that aren't finite
+    pub fn inverse(&self) -> Option<Mat4> {
+        if !self.is_finite() {
+            return None;
+        }
+
+        // Gauss-Jordan elimination with partial pivoting
+        let mut a = self.m;
+        let mut inverse = Mat4::identity().m;
+        for col in 0..4 {
+            let pivot = (col..4)
+                .max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))
+                .unwrap();
+            if a[pivot][col].abs() < 1e-12 {
+                return None;
+            }
+            a.swap(col, pivot);
+            inverse.swap(col, pivot);
+
+            let scale = 1.0 / a[col][col];
+            for i in 0..4 {
+                a[col][i] *= scale;
+                inverse[col][i] *= scale;
+            }
+            for row in 0..4 {
+                let factor = a[row][col];
+                if row == col || factor == 0.0 {
+                    continue;
+                }
+                for i in 0..4 {
+                    a[row][i] -= factor * a[col][i];
+                    inverse[row][i] -= factor * inverse[col][i];
+                }
+            }
+        }
+        let inverse = Mat4 { m: inverse };
+        if inverse.is_finite() {
+            Some(inverse)
+        } else {
+            None
+        }
+    }
+
+    fn is_finite(&self) -> bool {
+        self.m.iter().flatten().all(|value| value.is_finite())
+    }
+
+    pub fn transform_point(&self, p: Vec3) -> Vec3 {
+        self.transform_vector(p) + Vec3::new(self.m[0][3], self.m[1][3], self.m[2][3])
+    }
+
+    /// Transform a direction, which the translation doesn't apply to
+    pub fn transform_vector(&self, v: Vec3) -> Vec3 {
+        let m = &self.m;
+        Vec3::new(
+            m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z,
+            m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z,
+            m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z,
+        )
+    }
+}
+
+impl Default for Mat4 {
+    fn default() -> Mat4 {
+        Mat4::identity()
+    }
+}
+
+impl Mul for Mat4 {
+    type Output = Mat4;
+
+    fn mul(self, other: Mat4) -> Mat4 {
+        let mut m = [[0.0; 4]; 4];
+        for (row, values) in m.iter_mut().enumerate() {
+            for (col, value) in values.iter_mut().enumerate() {
+                *value = (0..4).map(|i| self.m[row][i] * other.m[i][col]).sum();
+            }
+        }
+        Mat4 { m }
+    }
+}
+
+/// An invertible affine transform along with its inverse, so points,
+/// directions and normals can be taken both ways. Composes with `*` like Mat4.
+#[derive(Copy, Clone, Debug)]
+pub struct Transform {
+    matrix: Mat4,
+    inverse: Mat4,
+}
+
+impl Transform {
+    /// None if matrix can't be inverted, like scales by 0
+    pub fn try_new(matrix: Mat4) -> Option<Transform> {
+        let inverse = matrix.inverse()?;
+        Some(Transform { matrix, inverse })
+    }
+
+    pub fn identity() -> Transform {
+        Transform {
+            matrix: Mat4::identity(),
+            inverse: Mat4::identity(),
+        }
+    }
+
+    pub fn matrix(&self) -> &Mat4 {
+        &self.matrix
+    }
+
+    pub fn inverse(&self) -> Transform {
+        Transform {
+            matrix: self.inverse,
+            inverse: self.matrix,
+        }
+    }
+
+    pub fn determinant(&self) -> f32 {
+        self.matrix.determinant()
+    }
+
+    pub fn transform_point(&self, p: Vec3) -> Vec3 {
+        self.matrix.transform_point(p)
+    }
+
+    pub fn transform_vector(&self, v: Vec3) -> Vec3 {
+        self.matrix.transform_vector(v)
+    }
+
+    /// How much the transform scales lengths, if it scales them the same
+    /// way in every direction
+    pub fn uniform_scale(&self) -> Option<f32> {
+        let x = self.transform_vector(Vec3::new(1.0, 0.0, 0.0));
+        let y = self.transform_vector(Vec3::new(0.0, 1.0, 0.0));
+        let z = self.transform_vector(Vec3::new(0.0, 0.0, 1.0));
+        let scale = x.length();
+        let tolerance = 1e-4 * scale;
+        let same_lengths =
+            (y.length() - scale).abs() < tolerance && (z.length() - scale).abs() < tolerance;
+        let square = x.dot(&y).abs() < tolerance * scale
+            && y.dot(&z).abs() < tolerance * scale
+            && z.dot(&x).abs() < tolerance * scale;
+        if same_lengths && square {
+            Some(scale)
+        } else {
+            None
+        }
+    }
+
+    /// Transform a surface normal so it stays perpendicular to the
+    /// transformed surface. The result isn't unit length.
+    pub fn transform_normal(&self, n: Vec3) -> Vec3 {
+        self.inverse.transpose().transform_vector(n)
+    }
+
+    /// The axis aligned box holding the transformed corners of bbox
+    pub fn transform_bounding_box(&self, bbox: &BoundingBox) -> BoundingBox {
+        let mut min = Vec3::new(f32::MAX, f32::MAX, f32::MAX);
+        let mut max = Vec3::new(-f32::MAX, -f32::MAX, -f32::MAX);
+        for i in 0..8 {
+            let corner = Vec3::new(
+                if i & 1 == 0 { bbox.min.x } else { bbox.max.x },
+                if i & 2 == 0 { bbox.min.y } else { bbox.max.y },
+                if i & 4 == 0 { bbox.min.z } else { bbox.max.z },
+            );
+            let p = self.transform_point(corner);
+            min = Vec3::new(min.x.min(p.x), min.y.min(p.y), min.z.min(p.z));
+            max = Vec3::new(max.x.max(p.x), max.y.max(p.y), max.z.max(p.z));
+        }
+        BoundingBox { min, max }
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Transform {
+        Transform::identity()
+    }
+}
+
+impl Mul for Transform {
+    type Output = Transform;
+
+    fn mul(self, other: Transform) -> Transform {
+        Transform {
+            matrix: self.matrix * other.matrix,
+            inverse: other.inverse * self.inverse,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: Vec3, b: Vec3) -> bool {
+        (a - b).length() < 1e-4
+    }
+
+    #[test]
+    fn composed_transforms_undo_with_their_inverse() {
+        let transform = Transform::try_new(
+            Mat4::translation(Vec3::new(1.0, 2.0, 3.0))
+                * Mat4::rotation(Vec3::new(1.0, 1.0, 0.0), 30.0)
+                * Mat4::scale(2.0, 0.5, -1.0),
+        )
+        .unwrap();
+        let p = Vec3::new(0.3, -0.7, 1.1);
+        assert!(close(
+            transform
+                .inverse()
+                .transform_point(transform.transform_point(p)),
+            p
+        ));
+        assert!(transform.determinant() < 0.0);
+
+        let q = Mat4::rotation_quaternion(0.0, (0.5f32).sqrt(), 0.0, (0.5f32).sqrt());
+        let y = Mat4::rotation_y(90.0);
+        assert!(close(
+            q.transform_vector(Vec3::new(1.0, 0.0, 0.0)),
+            y.transform_vector(Vec3::new(1.0, 0.0, 0.0))
+        ));
+        assert!(close(
+            y.transform_vector(Vec3::new(1.0, 0.0, 0.0)),
+            Vec3::new(0.0, 0.0, -1.0)
+        ));
+        assert!(Transform::try_new(Mat4::scale(1.0, 0.0, 1.0)).is_none());
+        assert!(Mat4::scale(f32::INFINITY, 1.0, 1.0).inverse().is_none());
+        assert!(Mat4::scale(1e30, 1.0, 1e30).inverse().is_some());
+    }
+
+    #[test]
+    fn normals_stay_perpendicular_under_scaling() {
+        let transform = Transform::try_new(
+            Mat4::rotation_z(20.0) * Mat4::scale(3.0, 1.0, 0.5) * Mat4::rotation_x(45.0),
+        )
+        .unwrap();
+        let tangent = Vec3::new(1.0, -1.0, 0.5);
+        let normal = Vec3::new(1.0, 1.0, 0.0);
+        let moved_tangent = transform.transform_vector(tangent);
+        let moved_normal = transform.transform_normal(normal);
+        assert!(moved_tangent.dot(&moved_normal).abs() < 1e-4);
+
+        let bbox = transform.transform_bounding_box(&BoundingBox {
+            min: Vec3::new(-1.0, -1.0, -1.0),
+            max: Vec3::new(1.0, 1.0, 1.0),
+        });
+        let corner = transform.transform_point(Vec3::new(1.0, -1.0, 1.0));
+        assert!(corner.x <= bbox.max.x && corner.y >= bbox.min.y);
+    }
+}
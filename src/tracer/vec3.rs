@@ -1,7 +1,8 @@
+use super::rng::sample_rng;
 use rand::prelude::*;
 use std::{fmt, ops};
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Default)]
 pub struct Vec3 {
     pub x: f32,
     pub y: f32,
@@ -14,7 +15,7 @@ impl Vec3 {
     }
 
     pub fn random_in_unit_sphere() -> Vec3 {
-        let mut rng = thread_rng();
+        let mut rng = sample_rng();
         let mut rnd = || rng.gen::<f32>();
         let mut sample = || 2.0 * Vec3::new(rnd(), rnd(), rnd()) - Vec3::new(1.0, 1.0, 1.0);
         let mut p = sample();
@@ -25,7 +26,7 @@ impl Vec3 {
     }
 
     pub fn random_in_unit_disk() -> Vec3 {
-        let mut rng = thread_rng();
+        let mut rng = sample_rng();
         let mut rnd = || rng.gen::<f32>();
         let mut sample = || 2.0 * Vec3::new(rnd(), rnd(), 0.0) - Vec3::new(1.0, 1.0, 0.0);
         let mut p = sample();
@@ -1,4 +1,9 @@
+use super::output::read_exr;
+use super::tracer::Vec3;
 use image;
+use image::hdr::HDRDecoder;
+use std::fs::File;
+use std::io::{self, BufReader};
 
 /// Returns a tuple with the image data, and its nx, ny values
 pub fn read_image(path: String) -> (Vec<u8>, u32, u32) {
@@ -8,6 +13,45 @@ pub fn read_image(path: String) -> (Vec<u8>, u32, u32) {
     (data, nx, ny)
 }
 
+/// Returns a tuple with linear colors, in rows top to bottom, and the image's
+/// nx, ny values. .hdr and .exr images are read as is, 8 bit images are
+/// assumed to be gamma 2 encoded.
+pub fn read_hdr_image(path: &str) -> io::Result<(Vec<Vec3>, u32, u32)> {
+    let extension = path.rsplit('.').next().unwrap_or("").to_lowercase();
+    let to_io_error = |e: image::ImageError| io::Error::new(io::ErrorKind::InvalidData, e);
+    match extension.as_str() {
+        "exr" => read_exr(path),
+        "hdr" => {
+            let decoder =
+                HDRDecoder::new(BufReader::new(File::open(path)?)).map_err(to_io_error)?;
+            let metadata = decoder.metadata();
+            let pixels = decoder
+                .read_image_hdr()
+                .map_err(to_io_error)?
+                .iter()
+                .map(|rgb| Vec3::new(rgb.data[0], rgb.data[1], rgb.data[2]))
+                .collect();
+            Ok((pixels, metadata.width, metadata.height))
+        }
+        _ => {
+            let pic = image::open(path).map_err(to_io_error)?.to_rgb();
+            let (nx, ny) = pic.dimensions();
+            let pixels = pic
+                .pixels()
+                .map(|rgb| {
+                    let channel = |c: u8| (c as f32 / 255.0).powi(2);
+                    Vec3::new(
+                        channel(rgb.data[0]),
+                        channel(rgb.data[1]),
+                        channel(rgb.data[2]),
+                    )
+                })
+                .collect();
+            Ok((pixels, nx, ny))
+        }
+    }
+}
+
 pub trait FloatCmp<T> {
     /// Panics if compared float is not within 0.000001 of self
     fn assert_nearly_eq(&self, f2: T);